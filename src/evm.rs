@@ -1,106 +1,549 @@
+pub mod bytecode;
 pub mod constants;
+pub mod env;
+pub mod frame;
+pub mod gas;
+pub mod i256;
+pub mod instructions;
+pub mod journal;
+pub mod memory;
 pub mod opcodes;
+pub mod receipt;
+pub mod spec;
+pub mod stack;
+pub mod state;
 pub mod utils;
 
-use std::collections::HashMap;
+use std::{mem, sync::Arc};
 
-use self::utils::{get_jumpdests, get_opcodes};
+use self::{
+    bytecode::{AnalyzedBytecode, BytecodeCache},
+    constants::{CALL_DEPTH_LIMIT, MAX_CODE_SIZE},
+    env::{BlockEnv, TxEnv},
+    frame::{CallContext, CallFrame, CallOutcome, CreateScheme, ReturnTarget, Transfer},
+    gas::{Gas, GasSchedule},
+    instructions::{instruction_table, InstructionTable},
+    memory::Memory,
+    opcodes::system,
+    spec::SpecId,
+    stack::Stack,
+    state::WorldState,
+    utils::{create2_address, create_address},
+};
 use crate::utils::{
     logger::Logger,
-    types::{EvmResult, ExecutionData, Logs, NextAction},
+    types::{EvmError, EvmResult, ExecutionData, ExitReason, NextAction, OpcodeResult},
 };
-use primitive_types::U256;
+use primitive_types::{H160, U256};
 
 #[derive(Debug)]
 pub struct EVM {
-    pub jumpdests: Vec<usize>,
-    pub memory: Vec<u8>,
-    pub storage: HashMap<usize, U256>,
-    pub msize: usize,
+    /// Hardfork whose rules are followed
+    pub spec: SpecId,
+    pub tx: TxEnv,
+    pub block: BlockEnv,
+    pub code: Arc<AnalyzedBytecode>,
+    pub code_cache: BytecodeCache,
+    pub memory: Memory,
+    /// Accounts of the fixture, as modified by the execution
+    pub state: WorldState,
+    /// Whether BLOCKHASH reads hashes older than 256 blocks from the
+    /// history contract of EIP-2935. Only from Prague
+    pub serve_history_hashes: bool,
+    /// Whether storage written by an execution is kept for the next ones.
+    /// Otherwise every execution only sees the storage of its fixture
+    pub persist_storage: bool,
+    /// Whether the whole state left by an execution is the one the next
+    /// ones start from. Checkpoints taken on `state` then stay valid
+    /// across executions
+    pub persist_state: bool,
     pub pc: usize,
-    pub stack: Vec<U256>,
-    pub logs: Logs,
-    pub result: String,
+    pub stack: Stack,
+    pub gas: Gas,
+    pub context: CallContext,
+    /// Suspended callers of the current frame, the innermost last
+    pub frames: Vec<CallFrame>,
+    /// Output of RETURN or REVERT
+    pub result: Vec<u8>,
+    /// Output of the last sub-call made by the current frame
+    pub return_data: Vec<u8>,
 }
 
 impl<'a> Logger<'a> for EVM {
     const NAMESPACE: &'a str = "EVM";
 }
 
+impl Default for EVM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl EVM {
     pub fn new() -> EVM {
-        let evm = EVM {
-            stack: Vec::with_capacity(1024),
-            // TODO: size may change; zero is a special value
-            // which does not require copy
-            memory: vec![0; 256],
-            storage: HashMap::new(),
+        EVM::with_spec(SpecId::default())
+    }
+
+    pub fn with_spec(spec: SpecId) -> EVM {
+        EVM {
+            spec,
+            tx: TxEnv::default(),
+            block: BlockEnv::default(),
+            stack: Stack::new(),
+            gas: Gas::unlimited(),
+            memory: Memory::new(),
+            state: WorldState::new(),
+            serve_history_hashes: false,
+            persist_storage: false,
+            persist_state: false,
             pc: 0,
-            msize: 0,
-            jumpdests: Vec::new(),
-            logs: Logs::new(),
-            result: String::new(),
+            code: Arc::new(AnalyzedBytecode::new(&[])),
+            code_cache: BytecodeCache::default(),
+            context: CallContext::default(),
+            frames: Vec::new(),
+            result: Vec::new(),
+            return_data: Vec::new(),
+        }
+    }
+
+    pub fn execute(&mut self, data: ExecutionData) -> EvmResult {
+        self.code = self.code_cache.get_or_analyze(data.bytecode);
+
+        let reason = match self
+            .init_env(&data)
+            .and_then(|_| self.init_state(&data))
+            .and_then(|_| self.init_gas())
+        {
+            Ok(()) => {
+                self.init_context();
+                self.init_access();
+
+                let checkpoint = self.state.checkpoint();
+                let reason = self.run(&data);
+
+                // a failed execution leaves no trace, except for the revert payload
+                if reason.is_success() {
+                    self.state.finish_transaction();
+                    self.state.commit();
+                    reason
+                } else {
+                    match self.state.revert_to(checkpoint) {
+                        Ok(()) => reason,
+                        Err(error) => ExitReason::Error(error),
+                    }
+                }
+            }
+            Err(error) => ExitReason::Error(error),
+        };
+
+        if let ExitReason::Error(_) = reason {
+            self.gas.consume_all();
+        }
+
+        let result = self.get_result(reason);
+        self.reset();
+
+        result
+    }
+
+    /// Transaction and block are parsed once, before the execution
+    fn init_env(&mut self, data: &ExecutionData) -> Result<(), EvmError> {
+        self.tx = TxEnv::from_fixture(data.tx.as_ref())?;
+        self.block = BlockEnv::from_fixture(data.block.as_ref())?;
+
+        Ok(())
+    }
+
+    /// Every execution starts from the state of the fixture, along with
+    /// the storage of the previous executions if it persists. The slots
+    /// of the fixture take precedence over the persisted ones. When the
+    /// whole state persists, the fixture is written over it instead
+    fn init_state(&mut self, data: &ExecutionData) -> Result<(), EvmError> {
+        if self.persist_state {
+            if let Some(state) = data.state {
+                self.state.apply_fixture(state)?;
+            }
+        } else {
+            let mut state = match data.state {
+                Some(state) => WorldState::from_fixture(state)?,
+                None => WorldState::new(),
+            };
+
+            let previous = mem::take(&mut self.state);
+            if self.persist_storage {
+                state.take_storage(previous);
+            }
+
+            self.state = state;
+        }
+
+        self.state.start_transaction();
+
+        Ok(())
+    }
+
+    /// The gas limit comes from the transaction, and the intrinsic cost
+    /// is paid out of it. Without a limit the execution is not metered
+    fn init_gas(&mut self) -> Result<(), EvmError> {
+        self.gas = match self.tx.gas_limit {
+            Some(limit) => Gas::new(limit),
+            None => Gas::unlimited(),
+        };
+
+        self.gas
+            .record_cost(gas::intrinsic_gas(&self.tx, self.spec))
+            .map_err(|_| EvmError::IntrinsicGasTooLow)
+    }
+
+    /// The outermost frame runs on behalf of the transaction
+    fn init_context(&mut self) {
+        self.context = CallContext {
+            address: self.tx.to,
+            caller: self.tx.caller,
+            code_address: self.tx.to,
+            value: self.tx.value,
+            input: self.tx.data.clone(),
+            is_static: false,
+        };
+    }
+
+    /// Warms what every transaction accesses, along with its access
+    /// list (EIP-2929, EIP-2930). The coinbase is warm since EIP-3651
+    fn init_access(&mut self) {
+        if !self.spec.is_enabled_in(SpecId::Berlin) {
+            return;
+        }
+
+        self.state.warm_address(&self.tx.origin);
+        self.state.warm_address(&self.tx.caller);
+        self.state.warm_address(&self.tx.to);
+        for precompile in 1..=self.spec.precompile_count() {
+            self.state.warm_address(&H160::from_low_u64_be(precompile));
+        }
+        if self.spec.is_enabled_in(SpecId::Shanghai) {
+            self.state.warm_address(&self.block.coinbase);
+        }
+
+        for item in &self.tx.access_list {
+            self.state.warm_address(&item.address);
+            for key in &item.storage_keys {
+                self.state.warm_slot(&item.address, *key);
+            }
+        }
+    }
+
+    /// Starts a sub-call in a new frame, which the interpreter runs next.
+    /// Once the sub-call is over, its outcome is handed to the caller as
+    /// told by `target`. A sub-call which cannot start hands it back at once
+    pub fn call(
+        &mut self,
+        context: CallContext,
+        gas: Gas,
+        transfer: Option<Transfer>,
+        target: ReturnTarget,
+    ) -> OpcodeResult {
+        let (code, hash) = self.state.code_with_hash(&context.code_address);
+        let code = self.code_cache.get_or_analyze_with_hash(code, hash);
+
+        self.enter(context, gas, code, transfer, target)
+    }
+
+    /// Starts the creation of a new contract, running `init_code` in a new
+    /// frame. The caller gets the address of the contract once it is over.
+    ///
+    /// The nonce of the creator is incremented even if the creation fails,
+    /// as long as the creation is attempted
+    pub fn create(
+        &mut self,
+        scheme: CreateScheme,
+        value: U256,
+        init_code: &[u8],
+        gas: Gas,
+    ) -> OpcodeResult {
+        let creator = self.context.address;
+
+        let address = match self.prepare_create(&creator, scheme, value, init_code) {
+            Ok(address) => address,
+            Err(error) => {
+                let target = ReturnTarget::Create {
+                    address: H160::zero(),
+                };
+                return self.resume(CallOutcome::failed(error, gas), target);
+            }
+        };
+        let target = ReturnTarget::Create { address };
+        // stays warm even if the creation fails
+        self.state.warm_address(&address);
+
+        if self.state.nonce(&address) != 0 || !self.state.code(&address).is_empty() {
+            let mut gas = gas;
+            gas.consume_all();
+            return self.resume(CallOutcome::failed(EvmError::CreateCollision, gas), target);
+        }
+
+        let context = CallContext {
+            address,
+            caller: creator,
+            code_address: address,
+            value,
+            input: Vec::new(),
+            is_static: false,
+        };
+        let transfer = Transfer {
+            from: creator,
+            to: address,
+            value,
         };
+        let code = self.code_cache.get_or_analyze(init_code);
 
-        evm
+        self.enter(context, gas, code, Some(transfer), target)
     }
 
-    pub fn execute(self: &mut Self, data: ExecutionData) -> EvmResult {
-        let opcodes = get_opcodes();
+    /// Checks that the creator can make a new contract, and bumps its nonce
+    fn prepare_create(
+        &mut self,
+        creator: &H160,
+        scheme: CreateScheme,
+        value: U256,
+        init_code: &[u8],
+    ) -> Result<H160, EvmError> {
+        if self.frames.len() >= CALL_DEPTH_LIMIT {
+            return Err(EvmError::CallTooDeep);
+        }
+        if self.state.balance(creator) < value {
+            return Err(EvmError::InsufficientBalance);
+        }
 
-        self.jumpdests = get_jumpdests(&data.bytecode);
+        let nonce = self.state.nonce(creator);
+        let next_nonce = nonce.checked_add(1).ok_or(EvmError::NonceOverflow)?;
+        self.state.set_nonce(creator, next_nonce);
 
-        let mut success = true;
+        Ok(match scheme {
+            CreateScheme::Create => create_address(creator, nonce),
+            CreateScheme::Create2 { salt } => create2_address(creator, &salt, init_code),
+        })
+    }
 
-        while self.pc < data.bytecode.len() {
-            let opcode_num = data.bytecode.get(self.pc).expect("Could not read bytecode");
+    /// Suspends the current frame, so that the interpreter runs `code`
+    /// in a new one. A creation deploys the output of the new frame as
+    /// the code of its account
+    fn enter(
+        &mut self,
+        context: CallContext,
+        gas: Gas,
+        code: Arc<AnalyzedBytecode>,
+        transfer: Option<Transfer>,
+        target: ReturnTarget,
+    ) -> OpcodeResult {
+        if self.frames.len() >= CALL_DEPTH_LIMIT {
+            return self.resume(CallOutcome::failed(EvmError::CallTooDeep, gas), target);
+        }
 
-            self.pc += 1;
+        let checkpoint = self.state.checkpoint();
 
-            let opcode = opcodes.get(&opcode_num).expect(&format!(
-                "Could not find function associated to opcode_num {:x}",
-                opcode_num
-            ));
+        if let ReturnTarget::Create { .. } = target {
+            // accounts of contracts start with a nonce of one (EIP-161)
+            if self.spec.is_enabled_in(SpecId::SpuriousDragon) {
+                self.state.set_nonce(&context.address, 1);
+            }
+            self.state.mark_created(&context.address);
+        }
 
-            let next_action = opcode(self, &data);
+        if let Some(transfer) = transfer {
+            if let Err(error) = self
+                .state
+                .transfer(&transfer.from, &transfer.to, transfer.value)
+            {
+                self.state.revert_to(checkpoint)?;
+                return self.resume(CallOutcome::failed(error, gas), target);
+            }
+        }
+
+        self.frames.push(CallFrame {
+            pc: mem::take(&mut self.pc),
+            stack: mem::take(&mut self.stack),
+            memory: mem::take(&mut self.memory),
+            gas: mem::replace(&mut self.gas, gas),
+            code: mem::replace(&mut self.code, code),
+            context: mem::replace(&mut self.context, context),
+            return_data: mem::take(&mut self.return_data),
+            result: mem::take(&mut self.result),
+            checkpoint,
+            target,
+        });
+
+        Ok(NextAction::Continue)
+    }
 
-            match next_action {
-                NextAction::Exit(status_code) => {
-                    success = status_code == 0;
-                    EVM::warning(&format!("Exiting with status code {}", status_code));
-                    break;
+    /// Ends the current sub-call, and resumes its caller with the outcome.
+    ///
+    /// The changes made by a failed sub-call are discarded, and its
+    /// gas is handed back whatever the result
+    fn exit(&mut self, mut reason: ExitReason) -> OpcodeResult {
+        let parent = self
+            .frames
+            .pop()
+            .expect("a sub-call always has a parent frame");
+
+        if let ReturnTarget::Create { .. } = parent.target {
+            if reason.is_success() {
+                if let Err(error) = self.deploy_code() {
+                    reason = ExitReason::Error(error);
                 }
-                _ => {}
             }
         }
 
-        let result = self.get_result(success);
-        self.reset();
+        if reason.is_success() {
+            self.state.commit();
+        } else if let Err(error) = self.state.revert_to(parent.checkpoint) {
+            reason = ExitReason::Error(error);
+        }
 
-        result
+        if let ExitReason::Error(_) = reason {
+            self.gas.consume_all();
+        }
+
+        self.pc = parent.pc;
+        self.stack = parent.stack;
+        self.memory = parent.memory;
+        self.code = parent.code;
+        self.context = parent.context;
+        self.return_data = parent.return_data;
+
+        let outcome = CallOutcome {
+            reason,
+            output: mem::replace(&mut self.result, parent.result),
+            gas: mem::replace(&mut self.gas, parent.gas),
+        };
+
+        self.resume(outcome, parent.target)
     }
 
-    fn reset(&mut self) -> () {
+    /// Hands the outcome of a sub-call to the current frame, its caller
+    fn resume(&mut self, outcome: CallOutcome, target: ReturnTarget) -> OpcodeResult {
+        match target {
+            ReturnTarget::Call {
+                ret_offset,
+                ret_size,
+            } => system::finish_call(self, outcome, ret_offset, ret_size),
+            ReturnTarget::Create { address } => system::finish_create(self, outcome, address),
+        }
+    }
+
+    /// Installs the output of the init code as the code of the new contract
+    fn deploy_code(&mut self) -> Result<(), EvmError> {
+        // EIP-3541 reserves the prefix for EOF contracts
+        if self.spec.is_enabled_in(SpecId::London) && self.result.first() == Some(&0xef) {
+            return Err(EvmError::InvalidCodePrefix);
+        }
+        // EIP-170
+        if self.spec.is_enabled_in(SpecId::SpuriousDragon) && self.result.len() > MAX_CODE_SIZE {
+            return Err(EvmError::CodeSizeLimit);
+        }
+
+        let deposit = gas::CODE_DEPOSIT * self.result.len() as u64;
+        // before Homestead, a contract which cannot pay for its code
+        // is created without code
+        if !self.spec.is_enabled_in(SpecId::Homestead) && self.gas.remaining() < deposit {
+            return Ok(());
+        }
+        self.gas.record_cost(deposit)?;
+
+        self.state
+            .set_code(&self.context.address, self.result.clone());
+
+        Ok(())
+    }
+
+    /// Runs the current frame and all of its sub-calls until it exits.
+    ///
+    /// Sub-calls are driven by this loop rather than by recursion, so
+    /// that deep call chains do not grow the native stack
+    fn run(&mut self, data: &ExecutionData) -> ExitReason {
+        let instructions = instruction_table(self.spec);
+
+        loop {
+            let mut next_action = self.step(instructions, data);
+
+            loop {
+                let reason = match next_action {
+                    Ok(NextAction::Continue) => break,
+                    Ok(NextAction::Exit(reason)) => reason,
+                    Err(error) => ExitReason::Error(error),
+                };
+
+                if self.frames.is_empty() {
+                    return reason;
+                }
+                // the caller may in turn exit, when it cannot take the outcome
+                next_action = self.exit(reason);
+            }
+        }
+    }
+
+    /// Executes the instruction at the program counter of the current frame
+    fn step(&mut self, instructions: &InstructionTable, data: &ExecutionData) -> OpcodeResult {
+        // running past the end of the code is an implicit STOP
+        if self.pc >= self.code.len() {
+            return Ok(NextAction::Exit(ExitReason::Stop));
+        }
+
+        let opcode_num = self.code.padded()[self.pc];
+
+        self.pc += 1;
+
+        let instruction = &instructions[usize::from(opcode_num)];
+
+        let next_action = self
+            .stack
+            .require(instruction.inputs, instruction.outputs)
+            .and_then(|_| self.gas.record_cost(instruction.static_gas))
+            .and_then(|_| (instruction.execute)(self, data));
+
+        match &next_action {
+            Ok(NextAction::Exit(reason)) => {
+                EVM::warning(&format!("Exiting with reason {:?}", reason))
+            }
+            Err(error) => EVM::warning(&format!("Exiting with reason {:?}", error)),
+            Ok(NextAction::Continue) => {}
+        }
+
+        next_action
+    }
+
+    fn reset(&mut self) {
         self.pc = 0;
         self.stack.clear();
-        self.memory = vec![0; 256];
-        self.msize = 0;
-        self.logs = Logs::new();
-        self.result = String::new();
+        self.memory.clear();
+        self.frames.clear();
+        self.result.clear();
+        self.return_data.clear();
     }
 
-    fn get_result(&self, success: bool) -> EvmResult {
-        let mut clone = self.stack.clone();
+    fn get_result(&self, reason: ExitReason) -> EvmResult {
+        let mut clone = self.stack.data().to_vec();
         clone.reverse();
 
+        // a failed transaction keeps no refund
+        let gas_refunded = if reason.is_success() {
+            let quotient = GasSchedule::new(self.spec).max_refund_quotient;
+            self.gas.final_refund(quotient)
+        } else {
+            0
+        };
+        let gas_remaining = match self.gas.limit() {
+            Some(_) => self.gas.remaining() + gas_refunded,
+            None => u64::MAX,
+        };
+
         EvmResult {
             stack: clone,
-            success,
-            logs: Logs {
-                address: self.logs.address.clone(),
-                data: self.logs.data.clone(),
-                topics: self.logs.topics.clone(),
-            },
+            success: reason.is_success(),
+            reason,
+            gas_used: self.gas.used() - gas_refunded,
+            gas_remaining,
+            gas_refunded,
+            logs: self.state.logs().to_vec(),
             result: self.result.clone(),
         }
     }
@@ -0,0 +1,180 @@
+use std::{collections::HashMap, sync::Arc};
+
+use primitive_types::H256;
+
+use super::{constants::JUMPDEST, utils::keccak256};
+
+/// Zero bytes appended to the code, so that a PUSH32 at the very end
+/// of the code can read its immediate without going out of bounds
+const PADDING: usize = 33;
+
+pub const DEFAULT_CACHE_CAPACITY: usize = 1024;
+
+/// Bytecode ready to be executed: padded, with its valid jump
+/// destinations and its hash computed once
+#[derive(Debug)]
+pub struct AnalyzedBytecode {
+    code: Vec<u8>,
+    len: usize,
+    jumpdests: Vec<u64>,
+    hash: H256,
+}
+
+impl AnalyzedBytecode {
+    pub fn new(bytecode: &[u8]) -> AnalyzedBytecode {
+        AnalyzedBytecode::with_hash(bytecode, keccak256(bytecode))
+    }
+
+    /// Analyzes `bytecode` whose hash is already known
+    pub fn with_hash(bytecode: &[u8], hash: H256) -> AnalyzedBytecode {
+        let mut code = Vec::with_capacity(bytecode.len() + PADDING);
+        code.extend_from_slice(bytecode);
+        code.resize(bytecode.len() + PADDING, 0);
+
+        AnalyzedBytecode {
+            code,
+            len: bytecode.len(),
+            jumpdests: find_jumpdests(bytecode),
+            hash,
+        }
+    }
+
+    /// Length of the original code, without padding
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The original code
+    pub fn code(&self) -> &[u8] {
+        &self.code[..self.len]
+    }
+
+    /// The code followed by enough zeros to read any PUSH immediate
+    pub fn padded(&self) -> &[u8] {
+        &self.code
+    }
+
+    pub fn hash(&self) -> H256 {
+        self.hash
+    }
+
+    pub fn is_jumpdest(&self, pc: usize) -> bool {
+        pc < self.len && self.jumpdests[pc / 64] & (1 << (pc % 64)) != 0
+    }
+}
+
+/// Reads the `bytecode` and returns a bitmap with all the indexes in
+/// which a jumpdest occurs, skipping the immediates of PUSH instructions.
+///
+/// Computational cost: O(n), where `n` is the length of the bytecode
+fn find_jumpdests(bytecode: &[u8]) -> Vec<u64> {
+    let mut jumpdests = vec![0u64; bytecode.len().div_ceil(64)];
+    let mut pc = 0;
+
+    while pc < bytecode.len() {
+        let opcode = bytecode[pc];
+
+        if (0x60..=0x7f).contains(&opcode) {
+            pc += usize::from(opcode - 0x5f);
+        } else if opcode == JUMPDEST {
+            jumpdests[pc / 64] |= 1 << (pc % 64);
+        }
+
+        pc += 1;
+    }
+
+    jumpdests
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    bytecode: Arc<AnalyzedBytecode>,
+    last_used: u64,
+}
+
+/// Least-recently-used cache of analyzed bytecode, keyed by code hash,
+/// so that contracts executed over and over are analyzed only once
+#[derive(Debug)]
+pub struct BytecodeCache {
+    capacity: usize,
+    entries: HashMap<H256, CacheEntry>,
+    clock: u64,
+}
+
+impl Default for BytecodeCache {
+    fn default() -> Self {
+        BytecodeCache::new(DEFAULT_CACHE_CAPACITY)
+    }
+}
+
+impl BytecodeCache {
+    pub fn new(capacity: usize) -> BytecodeCache {
+        BytecodeCache {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Returns the analysis of `bytecode`, computing it on a cache miss
+    pub fn get_or_analyze(&mut self, bytecode: &[u8]) -> Arc<AnalyzedBytecode> {
+        self.get_or_analyze_with_hash(bytecode, keccak256(bytecode))
+    }
+
+    /// Same as `get_or_analyze`, for code whose hash is already known
+    pub fn get_or_analyze_with_hash(
+        &mut self,
+        bytecode: &[u8],
+        hash: H256,
+    ) -> Arc<AnalyzedBytecode> {
+        self.clock += 1;
+
+        if let Some(entry) = self.entries.get_mut(&hash) {
+            entry.last_used = self.clock;
+            return entry.bytecode.clone();
+        }
+
+        if self.entries.len() >= self.capacity {
+            self.evict();
+        }
+
+        let bytecode = Arc::new(AnalyzedBytecode::with_hash(bytecode, hash));
+        self.entries.insert(
+            hash,
+            CacheEntry {
+                bytecode: bytecode.clone(),
+                last_used: self.clock,
+            },
+        );
+
+        bytecode
+    }
+
+    fn evict(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(hash, _)| *hash);
+
+        if let Some(hash) = oldest {
+            self.entries.remove(&hash);
+        }
+    }
+}
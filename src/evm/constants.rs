@@ -1 +1,35 @@
 pub const JUMPDEST: u8 = 0x5b;
+
+/// Maximum number of items on the stack
+pub const STACK_LIMIT: usize = 1024;
+
+/// Memory accesses past this size could never be paid for within a block
+/// gas limit, and are treated as invalid even when gas is not metered
+pub const MEMORY_LIMIT: usize = 32 * 1024 * 1024;
+
+/// Number of recent blocks whose hash BLOCKHASH can read
+pub const BLOCK_HASH_HISTORY: u64 = 256;
+
+/// Number of blocks whose hash the history contract keeps (EIP-2935)
+pub const HISTORY_SERVE_WINDOW: u64 = 8191;
+
+/// Address of the block hash history contract (EIP-2935)
+pub const HISTORY_STORAGE_ADDRESS: [u8; 20] = [
+    0x00, 0x00, 0xf9, 0x08, 0x27, 0xf1, 0xc5, 0x3a, 0x10, 0xcb, 0x7a, 0x02, 0x33, 0x5b, 0x17, 0x53,
+    0x20, 0x00, 0x29, 0x35,
+];
+
+/// Maximum depth of nested message calls
+pub const CALL_DEPTH_LIMIT: usize = 1024;
+
+/// Maximum size of the code of a contract (EIP-170)
+pub const MAX_CODE_SIZE: usize = 24576;
+
+/// Maximum size of the init code of a contract creation (EIP-3860)
+pub const MAX_INITCODE_SIZE: usize = 2 * MAX_CODE_SIZE;
+
+/// Hash of empty code, the code hash of every account without code
+pub const KECCAK_EMPTY: [u8; 32] = [
+    0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7, 0x03, 0xc0,
+    0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04, 0x5d, 0x85, 0xa4, 0x70,
+];
@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+
+use primitive_types::{H160, H256, U256};
+
+use super::{
+    constants::BLOCK_HASH_HISTORY,
+    utils::{keccak256, parse_hex_field, u256_to_address, u256_to_h256},
+};
+use crate::utils::types::{BlockData, Bytes, EvmError, TxData};
+
+/// Chain id of the Ethereum mainnet, used when the block does not set one
+pub const MAINNET_CHAIN_ID: u64 = 1;
+
+/// Block gas limit used when the block does not set one
+pub const DEFAULT_BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+/// Parses an optional hex field, using `default` when it is missing
+fn parse_u256(
+    value: Option<&String>,
+    field: &'static str,
+    default: U256,
+) -> Result<U256, EvmError> {
+    match value {
+        Some(_) => parse_hex_field(value, field),
+        None => Ok(default),
+    }
+}
+
+fn parse_u64(value: Option<&String>, field: &'static str, default: u64) -> Result<u64, EvmError> {
+    let value = parse_u256(value, field, U256::from(default))?;
+    if value > U256::from(u64::MAX) {
+        return Err(EvmError::FieldTooLarge(field));
+    }
+    Ok(value.as_u64())
+}
+
+fn parse_address(value: Option<&String>, field: &'static str) -> Result<H160, EvmError> {
+    let value = parse_u256(value, field, U256::zero())?;
+    if value.bits() > 160 {
+        return Err(EvmError::FieldTooLarge(field));
+    }
+    Ok(u256_to_address(value))
+}
+
+/// Address and storage keys warmed before the transaction runs (EIP-2930)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessListItem {
+    pub address: H160,
+    pub storage_keys: Vec<U256>,
+}
+
+/// Transaction being executed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxEnv {
+    /// Sender of the transaction, the CALLER of the outermost frame
+    pub caller: H160,
+    /// Account the transaction originates from, the sender if not set
+    pub origin: H160,
+    /// Account whose code is executed
+    pub to: H160,
+    /// Without a gas limit the execution is not metered
+    pub gas_limit: Option<u64>,
+    pub gas_price: U256,
+    pub value: U256,
+    pub data: Bytes,
+    /// Versioned hashes of the blobs carried by the transaction (EIP-4844)
+    pub blob_hashes: Vec<H256>,
+    pub access_list: Vec<AccessListItem>,
+}
+
+impl TxEnv {
+    /// Parses the transaction of a test fixture, failing on the first
+    /// malformed field. Missing fields take their default value
+    pub fn from_fixture(tx: Option<&TxData>) -> Result<TxEnv, EvmError> {
+        let tx = match tx {
+            Some(tx) => tx,
+            None => return Ok(TxEnv::default()),
+        };
+
+        let caller = parse_address(tx.from.as_ref(), "tx.from")?;
+        let origin = match tx.origin {
+            Some(_) => parse_address(tx.origin.as_ref(), "tx.origin")?,
+            None => caller,
+        };
+        let gas_limit = match tx.gas {
+            Some(_) => Some(parse_u64(tx.gas.as_ref(), "tx.gas", 0)?),
+            None => None,
+        };
+        let data = match tx.data.as_ref() {
+            Some(data) => hex::decode(data).map_err(|_| EvmError::InvalidHex("tx.data"))?,
+            None => Bytes::new(),
+        };
+        let blob_hashes = tx
+            .blobhashes
+            .iter()
+            .flatten()
+            .map(|hash| parse_hex_field(Some(hash), "tx.blobhashes").map(u256_to_h256))
+            .collect::<Result<_, _>>()?;
+        let access_list = tx
+            .accesslist
+            .iter()
+            .flatten()
+            .map(|item| {
+                Ok(AccessListItem {
+                    address: parse_address(Some(&item.address), "tx.accesslist.address")?,
+                    storage_keys: item
+                        .storagekeys
+                        .iter()
+                        .map(|key| parse_hex_field(Some(key), "tx.accesslist.storagekeys"))
+                        .collect::<Result<_, _>>()?,
+                })
+            })
+            .collect::<Result<_, EvmError>>()?;
+
+        Ok(TxEnv {
+            caller,
+            origin,
+            to: parse_address(tx.to.as_ref(), "tx.to")?,
+            gas_limit,
+            gas_price: parse_u256(tx.gasprice.as_ref(), "tx.gasprice", U256::zero())?,
+            value: parse_u256(tx.value.as_ref(), "tx.value", U256::zero())?,
+            data,
+            blob_hashes,
+            access_list,
+        })
+    }
+}
+
+/// Block the transaction is included in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockEnv {
+    pub number: u64,
+    pub coinbase: H160,
+    pub timestamp: u64,
+    pub gas_limit: u64,
+    pub basefee: U256,
+    pub difficulty: U256,
+    /// Randomness of the beacon chain, which replaces the difficulty
+    /// since the Merge (EIP-4399)
+    pub prevrandao: U256,
+    pub chain_id: u64,
+    /// Hashes of recent blocks, by block number. Blocks of the last 256
+    /// which are missing get a deterministic hash, see `block_hash`
+    pub block_hashes: HashMap<u64, H256>,
+}
+
+impl Default for BlockEnv {
+    fn default() -> Self {
+        BlockEnv {
+            number: 0,
+            coinbase: H160::zero(),
+            timestamp: 0,
+            gas_limit: DEFAULT_BLOCK_GAS_LIMIT,
+            basefee: U256::zero(),
+            difficulty: U256::zero(),
+            prevrandao: U256::zero(),
+            chain_id: MAINNET_CHAIN_ID,
+            block_hashes: HashMap::new(),
+        }
+    }
+}
+
+impl BlockEnv {
+    /// Parses the block of a test fixture, failing on the first
+    /// malformed field. Missing fields take their default value
+    pub fn from_fixture(block: Option<&BlockData>) -> Result<BlockEnv, EvmError> {
+        let block = match block {
+            Some(block) => block,
+            None => return Ok(BlockEnv::default()),
+        };
+        let default = BlockEnv::default();
+        let difficulty = parse_u256(
+            block.difficulty.as_ref(),
+            "block.difficulty",
+            default.difficulty,
+        )?;
+
+        Ok(BlockEnv {
+            number: parse_u64(block.number.as_ref(), "block.number", default.number)?,
+            coinbase: parse_address(block.coinbase.as_ref(), "block.coinbase")?,
+            timestamp: parse_u64(
+                block.timestamp.as_ref(),
+                "block.timestamp",
+                default.timestamp,
+            )?,
+            gas_limit: parse_u64(block.gaslimit.as_ref(), "block.gaslimit", default.gas_limit)?,
+            basefee: parse_u256(block.basefee.as_ref(), "block.basefee", default.basefee)?,
+            difficulty,
+            prevrandao: parse_u256(block.prevrandao.as_ref(), "block.prevrandao", difficulty)?,
+            chain_id: parse_u64(block.chainid.as_ref(), "block.chainid", default.chain_id)?,
+            block_hashes: parse_block_hashes(block.blockhashes.as_ref())?,
+        })
+    }
+
+    /// Hash of block `number` if it is one of the 256 blocks before this
+    /// one. Blocks without a known hash get the hash of their number
+    pub fn block_hash(&self, number: u64) -> Option<H256> {
+        if number >= self.number || number < self.number.saturating_sub(BLOCK_HASH_HISTORY) {
+            return None;
+        }
+
+        let hash = match self.block_hashes.get(&number) {
+            Some(hash) => *hash,
+            None => keccak256(u256_to_h256(U256::from(number)).as_bytes()),
+        };
+        Some(hash)
+    }
+}
+
+fn parse_block_hashes(
+    hashes: Option<&HashMap<String, String>>,
+) -> Result<HashMap<u64, H256>, EvmError> {
+    let mut block_hashes = HashMap::new();
+
+    for (number, hash) in hashes.into_iter().flatten() {
+        let number = parse_u64(Some(number), "block.blockhashes", 0)?;
+        let hash = parse_hex_field(Some(hash), "block.blockhashes")?;
+        block_hashes.insert(number, u256_to_h256(hash));
+    }
+
+    Ok(block_hashes)
+}
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use primitive_types::{H160, H256, U256};
+
+use super::{
+    bytecode::AnalyzedBytecode, gas::Gas, journal::Checkpoint, memory::Memory, stack::Stack,
+};
+use crate::utils::types::{EvmError, ExitReason};
+
+/// The kinds of message call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+}
+
+/// How the address of a new contract is derived
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreateScheme {
+    /// From the address and the nonce of the creator
+    Create,
+    /// From the address of the creator, a salt and the hash of the init code
+    Create2 { salt: H256 },
+}
+
+/// Environment of the code being executed, as seen by ADDRESS, CALLER,
+/// CALLVALUE and the CALLDATA* opcodes
+#[derive(Debug, Clone, Default)]
+pub struct CallContext {
+    /// Account whose balance and storage are used
+    pub address: H160,
+    pub caller: H160,
+    /// Account the code is loaded from: differs from `address` in
+    /// CALLCODE and DELEGATECALL
+    pub code_address: H160,
+    pub value: U256,
+    pub input: Vec<u8>,
+    /// Whether state modifications are forbidden, as in STATICCALL
+    pub is_static: bool,
+}
+
+/// Value moved from one account to another when entering a call
+#[derive(Debug, Clone, Copy)]
+pub struct Transfer {
+    pub from: H160,
+    pub to: H160,
+    pub value: U256,
+}
+
+/// What the caller does with the outcome of its sub-call once it resumes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReturnTarget {
+    /// A message call copies the output to this region of its memory
+    Call { ret_offset: usize, ret_size: usize },
+    /// A creation pushes the address of the new contract
+    Create { address: H160 },
+}
+
+/// Execution state of a suspended caller, restored when the sub-call returns
+#[derive(Debug)]
+pub struct CallFrame {
+    pub pc: usize,
+    pub stack: Stack,
+    pub memory: Memory,
+    pub gas: Gas,
+    pub code: Arc<AnalyzedBytecode>,
+    pub context: CallContext,
+    pub return_data: Vec<u8>,
+    pub result: Vec<u8>,
+    /// Taken when the sub-call started, a failed sub-call reverts to it
+    pub checkpoint: Checkpoint,
+    pub target: ReturnTarget,
+}
+
+/// What a sub-call hands back to its caller
+#[derive(Debug)]
+pub struct CallOutcome {
+    pub reason: ExitReason,
+    pub output: Vec<u8>,
+    /// Gas meter of the sub-call, whose leftover goes back to the caller
+    pub gas: Gas,
+}
+
+impl CallOutcome {
+    /// Outcome of a sub-call that could not start, which hands back
+    /// its gas untouched
+    pub fn failed(error: EvmError, gas: Gas) -> CallOutcome {
+        CallOutcome {
+            reason: ExitReason::Error(error),
+            output: Vec::new(),
+            gas,
+        }
+    }
+}
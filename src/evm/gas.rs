@@ -0,0 +1,326 @@
+use primitive_types::U256;
+
+use super::{env::TxEnv, spec::SpecId};
+use crate::utils::types::EvmError;
+
+pub const ZERO: u64 = 0;
+pub const BASE: u64 = 2;
+pub const VERYLOW: u64 = 3;
+pub const LOW: u64 = 5;
+pub const MID: u64 = 8;
+pub const HIGH: u64 = 10;
+pub const JUMPDEST: u64 = 1;
+pub const EXP: u64 = 10;
+pub const EXP_BYTE: u64 = 50;
+pub const SHA3: u64 = 30;
+pub const SHA3_WORD: u64 = 6;
+pub const COPY: u64 = 3;
+pub const MEMORY: u64 = 3;
+pub const QUAD_COEFF_DIV: u64 = 512;
+pub const LOG: u64 = 375;
+pub const LOG_DATA: u64 = 8;
+pub const LOG_TOPIC: u64 = 375;
+pub const BLOCKHASH: u64 = 20;
+pub const BALANCE: u64 = 700;
+pub const EXTCODE: u64 = 700;
+pub const SLOAD: u64 = 800;
+/// Cost of reading a slot already accessed, and of transient storage
+pub const WARM_STORAGE_READ: u64 = 100;
+/// Cost of the first access to an account in a transaction (EIP-2929)
+pub const COLD_ACCOUNT_ACCESS: u64 = 2600;
+/// Cost of the first access to a storage slot in a transaction (EIP-2929)
+pub const COLD_SLOAD: u64 = 2100;
+pub const SSTORE_SET: u64 = 20000;
+pub const SSTORE_RESET: u64 = 5000;
+pub const SSTORE_CLEARS_REFUND: i64 = 15000;
+/// Cost every transaction pays before its execution
+pub const TX: u64 = 21000;
+pub const TX_DATA_ZERO: u64 = 4;
+/// Lowered from 68 by EIP-2028 in Istanbul
+pub const TX_DATA_NON_ZERO: u64 = 16;
+/// Cost of an address of an access list (EIP-2930)
+pub const ACCESS_LIST_ADDRESS: u64 = 2400;
+/// Cost of a storage key of an access list (EIP-2930)
+pub const ACCESS_LIST_STORAGE_KEY: u64 = 1900;
+pub const CREATE: u64 = 32000;
+pub const CODE_DEPOSIT: u64 = 200;
+pub const INITCODE_WORD: u64 = 2;
+pub const CALL: u64 = 700;
+pub const CALL_VALUE: u64 = 9000;
+pub const CALL_STIPEND: u64 = 2300;
+pub const NEW_ACCOUNT: u64 = 25000;
+pub const SELFDESTRUCT: u64 = 5000;
+/// Removed by EIP-3529 in London
+pub const SELFDESTRUCT_REFUND: i64 = 24000;
+/// The refund is capped at the gas used divided by this (EIP-3529)
+pub const MAX_REFUND_QUOTIENT: u64 = 5;
+
+/// Costs which were repriced by hardforks. The constants above hold
+/// the ones of Istanbul, before access lists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasSchedule {
+    pub balance: u64,
+    pub extcode: u64,
+    pub extcodehash: u64,
+    pub sload: u64,
+    pub call: u64,
+    pub selfdestruct: u64,
+    pub exp_byte: u64,
+    /// Cost of changing a clean non-zero storage slot
+    pub sstore_reset: u64,
+    /// Refund for clearing a storage slot
+    pub sstore_clears_refund: i64,
+    /// The refund is capped at the gas used divided by this
+    pub max_refund_quotient: u64,
+}
+
+impl GasSchedule {
+    pub const fn new(spec: SpecId) -> GasSchedule {
+        let mut schedule = if spec.is_enabled_in(SpecId::Berlin) {
+            // EIP-2929, cold accesses are charged on top by the handlers
+            GasSchedule {
+                balance: WARM_STORAGE_READ,
+                extcode: WARM_STORAGE_READ,
+                extcodehash: WARM_STORAGE_READ,
+                sload: WARM_STORAGE_READ,
+                call: WARM_STORAGE_READ,
+                selfdestruct: SELFDESTRUCT,
+                exp_byte: EXP_BYTE,
+                sstore_reset: SSTORE_RESET - COLD_SLOAD,
+                sstore_clears_refund: SSTORE_CLEARS_REFUND,
+                max_refund_quotient: 2,
+            }
+        } else if spec.is_enabled_in(SpecId::Istanbul) {
+            // EIP-1884
+            GasSchedule {
+                balance: BALANCE,
+                extcode: EXTCODE,
+                extcodehash: EXTCODE,
+                sload: SLOAD,
+                call: CALL,
+                selfdestruct: SELFDESTRUCT,
+                exp_byte: EXP_BYTE,
+                sstore_reset: SSTORE_RESET,
+                sstore_clears_refund: SSTORE_CLEARS_REFUND,
+                max_refund_quotient: 2,
+            }
+        } else if spec.is_enabled_in(SpecId::TangerineWhistle) {
+            // EIP-150, EXTCODEHASH comes with Constantinople
+            GasSchedule {
+                balance: 400,
+                extcode: EXTCODE,
+                extcodehash: 400,
+                sload: 200,
+                call: CALL,
+                selfdestruct: SELFDESTRUCT,
+                // EIP-160
+                exp_byte: if spec.is_enabled_in(SpecId::SpuriousDragon) {
+                    EXP_BYTE
+                } else {
+                    10
+                },
+                sstore_reset: SSTORE_RESET,
+                sstore_clears_refund: SSTORE_CLEARS_REFUND,
+                max_refund_quotient: 2,
+            }
+        } else {
+            GasSchedule {
+                balance: 20,
+                extcode: 20,
+                extcodehash: 20,
+                sload: 50,
+                call: 40,
+                selfdestruct: 0,
+                exp_byte: 10,
+                sstore_reset: SSTORE_RESET,
+                sstore_clears_refund: SSTORE_CLEARS_REFUND,
+                max_refund_quotient: 2,
+            }
+        };
+
+        // EIP-3529
+        if spec.is_enabled_in(SpecId::London) {
+            schedule.sstore_clears_refund =
+                (SSTORE_RESET - COLD_SLOAD + ACCESS_LIST_STORAGE_KEY) as i64;
+            schedule.max_refund_quotient = MAX_REFUND_QUOTIENT;
+        }
+
+        schedule
+    }
+}
+
+/// Gas meter of an execution.
+///
+/// A meter without a limit never runs out of gas but still accounts
+/// for the gas used, which is what the test suite expects when the
+/// transaction does not specify a gas limit
+#[derive(Debug, Clone, Default)]
+pub struct Gas {
+    limit: Option<u64>,
+    used: u64,
+    refunded: i64,
+}
+
+impl Gas {
+    pub fn new(limit: u64) -> Gas {
+        Gas {
+            limit: Some(limit),
+            used: 0,
+            refunded: 0,
+        }
+    }
+
+    pub fn unlimited() -> Gas {
+        Gas {
+            limit: None,
+            used: 0,
+            refunded: 0,
+        }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.limit.is_none()
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        self.limit
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    /// Gas still available, `u64::MAX` when the meter has no limit
+    pub fn remaining(&self) -> u64 {
+        match self.limit {
+            Some(limit) => limit - self.used,
+            None => u64::MAX,
+        }
+    }
+
+    pub fn refunded(&self) -> i64 {
+        self.refunded
+    }
+
+    pub fn record_cost(&mut self, cost: u64) -> Result<(), EvmError> {
+        let used = self.used.checked_add(cost).ok_or(EvmError::OutOfGas)?;
+
+        if let Some(limit) = self.limit {
+            if used > limit {
+                self.used = limit;
+                return Err(EvmError::OutOfGas);
+            }
+        }
+
+        self.used = used;
+        Ok(())
+    }
+
+    /// Gas that can be handed to a sub-call: all but one 64th of what
+    /// is left (EIP-150)
+    pub fn all_but_one_64th(&self) -> u64 {
+        let remaining = self.remaining();
+        remaining - remaining / 64
+    }
+
+    /// Reserves the gas of a sub-call asking for `requested` gas,
+    /// and returns the meter of the sub-call. `stipend` is given for free.
+    /// Before EIP-150, asking for more gas than is left runs out of gas
+    pub fn forward(
+        &mut self,
+        requested: U256,
+        stipend: u64,
+        spec: SpecId,
+    ) -> Result<Gas, EvmError> {
+        if self.is_unlimited() {
+            return Ok(Gas::unlimited());
+        }
+
+        let available = if spec.is_enabled_in(SpecId::TangerineWhistle) {
+            self.all_but_one_64th()
+        } else if requested > U256::from(self.remaining()) {
+            return Err(EvmError::OutOfGas);
+        } else {
+            self.remaining()
+        };
+
+        let limit = requested.min(U256::from(available)).as_u64();
+        self.record_cost(limit)?;
+
+        Ok(Gas::new(limit + stipend))
+    }
+
+    /// Takes back the gas a sub-call did not spend. Without a limit,
+    /// the gas used by the sub-call is accounted instead
+    pub fn reclaim(&mut self, child: &Gas) {
+        match self.limit {
+            Some(_) => self.used = self.used.saturating_sub(child.remaining()),
+            None => self.used = self.used.saturating_add(child.used),
+        }
+    }
+
+    pub fn record_refund(&mut self, refund: i64) {
+        self.refunded += refund;
+    }
+
+    /// Refund granted at the end of the transaction: the refund counter,
+    /// capped at a fraction of the gas used
+    pub fn final_refund(&self, max_refund_quotient: u64) -> u64 {
+        let refunded = u64::try_from(self.refunded).unwrap_or_default();
+        refunded.min(self.used / max_refund_quotient)
+    }
+
+    /// An exceptional halt consumes all the gas of the execution
+    pub fn consume_all(&mut self) {
+        if let Some(limit) = self.limit {
+            self.used = limit;
+        }
+        self.refunded = 0;
+    }
+}
+
+/// Gas a transaction pays before its execution starts: the base cost
+/// along with the cost of its calldata and of its access list
+pub fn intrinsic_gas(tx: &TxEnv, spec: SpecId) -> u64 {
+    let non_zero_cost = if spec.is_enabled_in(SpecId::Istanbul) {
+        TX_DATA_NON_ZERO
+    } else {
+        68
+    };
+
+    let data_cost = tx.data.iter().fold(0, |cost, byte| {
+        cost + if *byte == 0 {
+            TX_DATA_ZERO
+        } else {
+            non_zero_cost
+        }
+    });
+
+    // access lists come with Berlin, and are ignored before it
+    let access_list_cost = if spec.is_enabled_in(SpecId::Berlin) {
+        tx.access_list.iter().fold(0, |cost, item| {
+            cost + ACCESS_LIST_ADDRESS + ACCESS_LIST_STORAGE_KEY * item.storage_keys.len() as u64
+        })
+    } else {
+        0
+    };
+
+    TX + data_cost + access_list_cost
+}
+
+/// Number of 32-bytes words needed to hold `size` bytes
+pub fn num_words(size: usize) -> u64 {
+    (size as u64).div_ceil(32)
+}
+
+/// Total cost of a memory of `words` words
+pub fn memory_cost(words: u64) -> u64 {
+    MEMORY
+        .saturating_mul(words)
+        .saturating_add(words.saturating_mul(words) / QUAD_COEFF_DIV)
+}
+
+/// Cost of copying `size` bytes, as done by CALLDATACOPY, CODECOPY and similar
+pub fn copy_cost(size: usize) -> u64 {
+    COPY.saturating_mul(num_words(size))
+}
@@ -0,0 +1,98 @@
+use std::cmp::Ordering;
+
+use primitive_types::U256;
+
+/// Bit holding the sign of a two's complement word
+const SIGN_BIT: U256 = U256([0, 0, 0, 1 << 63]);
+
+/// Signed 256-bit integer, stored as the two's complement word the stack
+/// holds. Operations wrap around, as the EVM does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct I256(pub U256);
+
+impl I256 {
+    /// -2^255, the only value whose negation is itself
+    pub const MIN: I256 = I256(SIGN_BIT);
+
+    pub fn zero() -> I256 {
+        I256(U256::zero())
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0.bit(255)
+    }
+
+    pub fn wrapping_neg(self) -> I256 {
+        I256((!self.0).overflowing_add(U256::one()).0)
+    }
+
+    /// Absolute value as an unsigned number, so that the one of MIN
+    /// does not overflow
+    pub fn unsigned_abs(self) -> U256 {
+        if self.is_negative() {
+            self.wrapping_neg().0
+        } else {
+            self.0
+        }
+    }
+
+    /// Division truncated towards zero, zero when dividing by zero.
+    /// MIN / -1 overflows back to MIN
+    pub fn signed_div(self, rhs: I256) -> I256 {
+        if rhs.0.is_zero() {
+            return I256::zero();
+        }
+
+        let quotient = I256(self.unsigned_abs() / rhs.unsigned_abs());
+        if self.is_negative() != rhs.is_negative() {
+            quotient.wrapping_neg()
+        } else {
+            quotient
+        }
+    }
+
+    /// Remainder of the truncated division, taking the sign of `self`.
+    /// Zero when dividing by zero
+    pub fn signed_rem(self, rhs: I256) -> I256 {
+        if rhs.0.is_zero() {
+            return I256::zero();
+        }
+
+        let remainder = I256(self.unsigned_abs() % rhs.unsigned_abs());
+        if self.is_negative() {
+            remainder.wrapping_neg()
+        } else {
+            remainder
+        }
+    }
+
+    /// Arithmetic shift to the right, filling with the sign bit
+    pub fn arithmetic_shr(self, shift: U256) -> I256 {
+        if shift >= U256::from(256) {
+            return if self.is_negative() {
+                I256(U256::MAX)
+            } else {
+                I256::zero()
+            };
+        }
+
+        if self.is_negative() {
+            I256(!(!self.0 >> shift))
+        } else {
+            I256(self.0 >> shift)
+        }
+    }
+}
+
+impl Ord for I256 {
+    /// Flipping the sign bit maps the signed order onto the unsigned one
+    fn cmp(&self, other: &I256) -> Ordering {
+        (self.0 ^ SIGN_BIT).cmp(&(other.0 ^ SIGN_BIT))
+    }
+}
+
+impl PartialOrd for I256 {
+    fn partial_cmp(&self, other: &I256) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
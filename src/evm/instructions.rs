@@ -0,0 +1,312 @@
+use super::{
+    gas::{self, GasSchedule},
+    opcodes,
+    spec::SpecId,
+};
+use crate::utils::types::Opcode;
+
+/// Everything the interpreter needs to know about an opcode before running it
+#[derive(Clone, Copy)]
+pub struct Instruction {
+    pub name: &'static str,
+    pub execute: Opcode,
+    /// Gas charged before the execution. Dynamic costs are charged by the handler
+    pub static_gas: u64,
+    /// Number of items popped from the stack
+    pub inputs: usize,
+    /// Number of items pushed to the stack
+    pub outputs: usize,
+}
+
+impl Instruction {
+    const fn new(
+        name: &'static str,
+        execute: Opcode,
+        static_gas: u64,
+        inputs: usize,
+        outputs: usize,
+    ) -> Instruction {
+        Instruction {
+            name,
+            execute,
+            static_gas,
+            inputs,
+            outputs,
+        }
+    }
+
+    /// Bytes which are not instructions halt the execution
+    const UNKNOWN: Instruction = Instruction::new("UNKNOWN", opcodes::misc::unknown, 0, 0, 0);
+}
+
+impl std::fmt::Debug for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// Dispatch table of the interpreter, indexed by opcode
+pub type InstructionTable = [Instruction; 256];
+
+static FRONTIER: InstructionTable = make_instruction_table(SpecId::Frontier);
+static HOMESTEAD: InstructionTable = make_instruction_table(SpecId::Homestead);
+static TANGERINE_WHISTLE: InstructionTable = make_instruction_table(SpecId::TangerineWhistle);
+static SPURIOUS_DRAGON: InstructionTable = make_instruction_table(SpecId::SpuriousDragon);
+static BYZANTIUM: InstructionTable = make_instruction_table(SpecId::Byzantium);
+static CONSTANTINOPLE: InstructionTable = make_instruction_table(SpecId::Constantinople);
+static PETERSBURG: InstructionTable = make_instruction_table(SpecId::Petersburg);
+static ISTANBUL: InstructionTable = make_instruction_table(SpecId::Istanbul);
+static BERLIN: InstructionTable = make_instruction_table(SpecId::Berlin);
+static LONDON: InstructionTable = make_instruction_table(SpecId::London);
+static MERGE: InstructionTable = make_instruction_table(SpecId::Merge);
+static SHANGHAI: InstructionTable = make_instruction_table(SpecId::Shanghai);
+static CANCUN: InstructionTable = make_instruction_table(SpecId::Cancun);
+static PRAGUE: InstructionTable = make_instruction_table(SpecId::Prague);
+
+/// Instructions available in `spec`, priced by its gas schedule
+pub fn instruction_table(spec: SpecId) -> &'static InstructionTable {
+    match spec {
+        SpecId::Frontier => &FRONTIER,
+        SpecId::Homestead => &HOMESTEAD,
+        SpecId::TangerineWhistle => &TANGERINE_WHISTLE,
+        SpecId::SpuriousDragon => &SPURIOUS_DRAGON,
+        SpecId::Byzantium => &BYZANTIUM,
+        SpecId::Constantinople => &CONSTANTINOPLE,
+        SpecId::Petersburg => &PETERSBURG,
+        SpecId::Istanbul => &ISTANBUL,
+        SpecId::Berlin => &BERLIN,
+        SpecId::London => &LONDON,
+        SpecId::Merge => &MERGE,
+        SpecId::Shanghai => &SHANGHAI,
+        SpecId::Cancun => &CANCUN,
+        SpecId::Prague => &PRAGUE,
+    }
+}
+
+macro_rules! push {
+    ($table:ident, $($n:literal),*) => {
+        $($table[0x5f + $n] = Instruction::new(
+            concat!("PUSH", $n),
+            opcodes::stack::push_n::<$n>,
+            gas::VERYLOW,
+            0,
+            1,
+        );)*
+    };
+}
+
+macro_rules! dup {
+    ($table:ident, $($n:literal),*) => {
+        $($table[0x7f + $n] = Instruction::new(
+            concat!("DUP", $n),
+            opcodes::stack::dup_n::<$n>,
+            gas::VERYLOW,
+            $n,
+            $n + 1,
+        );)*
+    };
+}
+
+macro_rules! swap {
+    ($table:ident, $($n:literal),*) => {
+        $($table[0x8f + $n] = Instruction::new(
+            concat!("SWAP", $n),
+            opcodes::stack::swap_n::<$n>,
+            gas::VERYLOW,
+            $n + 1,
+            $n + 1,
+        );)*
+    };
+}
+
+macro_rules! log {
+    ($table:ident, $($n:literal),*) => {
+        $($table[0xa0 + $n] = Instruction::new(
+            concat!("LOG", $n),
+            opcodes::logging::log_n::<$n>,
+            gas::LOG,
+            $n + 2,
+            0,
+        );)*
+    };
+}
+
+/// Opcodes introduced by a hardfork are left unknown before it
+const fn make_instruction_table(spec: SpecId) -> InstructionTable {
+    use opcodes::*;
+    use SpecId::*;
+
+    let schedule = GasSchedule::new(spec);
+    let mut table = [Instruction::UNKNOWN; 256];
+
+    table[0x00] = Instruction::new("STOP", misc::stop, gas::ZERO, 0, 0);
+    table[0x01] = Instruction::new("ADD", arithmetic::add, gas::VERYLOW, 2, 1);
+    table[0x02] = Instruction::new("MUL", arithmetic::mul, gas::LOW, 2, 1);
+    table[0x03] = Instruction::new("SUB", arithmetic::sub, gas::VERYLOW, 2, 1);
+    table[0x04] = Instruction::new("DIV", arithmetic::div, gas::LOW, 2, 1);
+    table[0x05] = Instruction::new("SDIV", arithmetic::s_div, gas::LOW, 2, 1);
+    table[0x06] = Instruction::new("MOD", arithmetic::modulo, gas::LOW, 2, 1);
+    table[0x07] = Instruction::new("SMOD", arithmetic::s_modulo, gas::LOW, 2, 1);
+    table[0x08] = Instruction::new("ADDMOD", arithmetic::add_mod, gas::MID, 3, 1);
+    table[0x09] = Instruction::new("MULMOD", arithmetic::mul_mod, gas::MID, 3, 1);
+    table[0x0a] = Instruction::new("EXP", arithmetic::exp, gas::EXP, 2, 1);
+    table[0x0b] = Instruction::new("SIGNEXTEND", arithmetic::sign_extend, gas::LOW, 2, 1);
+
+    table[0x10] = Instruction::new("LT", logic::lt, gas::VERYLOW, 2, 1);
+    table[0x11] = Instruction::new("GT", logic::gt, gas::VERYLOW, 2, 1);
+    table[0x12] = Instruction::new("SLT", logic::slt, gas::VERYLOW, 2, 1);
+    table[0x13] = Instruction::new("SGT", logic::sgt, gas::VERYLOW, 2, 1);
+    table[0x14] = Instruction::new("EQ", logic::eq, gas::VERYLOW, 2, 1);
+    table[0x15] = Instruction::new("ISZERO", logic::is_zero, gas::VERYLOW, 1, 1);
+    table[0x16] = Instruction::new("AND", logic::and, gas::VERYLOW, 2, 1);
+    table[0x17] = Instruction::new("OR", logic::or, gas::VERYLOW, 2, 1);
+    table[0x18] = Instruction::new("XOR", logic::xor, gas::VERYLOW, 2, 1);
+    table[0x19] = Instruction::new("NOT", logic::not, gas::VERYLOW, 1, 1);
+    table[0x1a] = Instruction::new("BYTE", misc::byte, gas::VERYLOW, 2, 1);
+    if spec.is_enabled_in(Constantinople) {
+        table[0x1b] = Instruction::new("SHL", misc::shl, gas::VERYLOW, 2, 1);
+        table[0x1c] = Instruction::new("SHR", misc::shr, gas::VERYLOW, 2, 1);
+        table[0x1d] = Instruction::new("SAR", misc::sar, gas::VERYLOW, 2, 1);
+    }
+
+    table[0x20] = Instruction::new("SHA3", misc::sha3, gas::SHA3, 2, 1);
+
+    table[0x30] = Instruction::new("ADDRESS", transaction::address, gas::BASE, 0, 1);
+    table[0x31] = Instruction::new("BALANCE", state::balance, schedule.balance, 1, 1);
+    table[0x32] = Instruction::new("ORIGIN", transaction::origin, gas::BASE, 0, 1);
+    table[0x33] = Instruction::new("CALLER", transaction::caller, gas::BASE, 0, 1);
+    table[0x34] = Instruction::new("CALLVALUE", transaction::callvalue, gas::BASE, 0, 1);
+    table[0x35] = Instruction::new(
+        "CALLDATALOAD",
+        transaction::calldataload,
+        gas::VERYLOW,
+        1,
+        1,
+    );
+    table[0x36] = Instruction::new("CALLDATASIZE", transaction::calldatasize, gas::BASE, 0, 1);
+    table[0x37] = Instruction::new(
+        "CALLDATACOPY",
+        transaction::calldatacopy,
+        gas::VERYLOW,
+        3,
+        0,
+    );
+    table[0x38] = Instruction::new("CODESIZE", misc::codesize, gas::BASE, 0, 1);
+    table[0x39] = Instruction::new("CODECOPY", misc::codecopy, gas::VERYLOW, 3, 0);
+    table[0x3a] = Instruction::new("GASPRICE", transaction::gasprice, gas::BASE, 0, 1);
+    table[0x3b] = Instruction::new(
+        "EXTCODESIZE",
+        environmental::extcodesize,
+        schedule.extcode,
+        1,
+        1,
+    );
+    table[0x3c] = Instruction::new(
+        "EXTCODECOPY",
+        environmental::extcodecopy,
+        schedule.extcode,
+        4,
+        0,
+    );
+    if spec.is_enabled_in(Byzantium) {
+        table[0x3d] = Instruction::new(
+            "RETURNDATASIZE",
+            environmental::returndatasize,
+            gas::BASE,
+            0,
+            1,
+        );
+        table[0x3e] = Instruction::new(
+            "RETURNDATACOPY",
+            environmental::returndatacopy,
+            gas::VERYLOW,
+            3,
+            0,
+        );
+    }
+    if spec.is_enabled_in(Constantinople) {
+        table[0x3f] = Instruction::new(
+            "EXTCODEHASH",
+            environmental::extcodehash,
+            schedule.extcodehash,
+            1,
+            1,
+        );
+    }
+
+    table[0x40] = Instruction::new("BLOCKHASH", block::blockhash, gas::BLOCKHASH, 1, 1);
+    table[0x41] = Instruction::new("COINBASE", block::coinbase, gas::BASE, 0, 1);
+    table[0x42] = Instruction::new("TIMESTAMP", block::timestamp, gas::BASE, 0, 1);
+    table[0x43] = Instruction::new("NUMBER", block::number, gas::BASE, 0, 1);
+    // EIP-4399 repurposes the opcode once blocks are no longer mined
+    table[0x44] = if spec.is_enabled_in(Merge) {
+        Instruction::new("PREVRANDAO", block::prevrandao, gas::BASE, 0, 1)
+    } else {
+        Instruction::new("DIFFICULTY", block::difficulty, gas::BASE, 0, 1)
+    };
+    table[0x45] = Instruction::new("GASLIMIT", block::gaslimit, gas::BASE, 0, 1);
+    if spec.is_enabled_in(Istanbul) {
+        table[0x46] = Instruction::new("CHAINID", block::chain, gas::BASE, 0, 1);
+        table[0x47] = Instruction::new("SELFBALANCE", block::selfbalance, gas::LOW, 0, 1);
+    }
+    if spec.is_enabled_in(London) {
+        table[0x48] = Instruction::new("BASEFEE", block::basefee, gas::BASE, 0, 1);
+    }
+    if spec.is_enabled_in(Cancun) {
+        table[0x49] = Instruction::new("BLOBHASH", transaction::blobhash, gas::VERYLOW, 1, 1);
+    }
+
+    table[0x50] = Instruction::new("POP", stack::pop, gas::BASE, 1, 0);
+    table[0x51] = Instruction::new("MLOAD", memory::mload, gas::VERYLOW, 1, 1);
+    table[0x52] = Instruction::new("MSTORE", memory::mstore, gas::VERYLOW, 2, 0);
+    table[0x53] = Instruction::new("MSTORE8", memory::mstore8, gas::VERYLOW, 2, 0);
+    table[0x54] = Instruction::new("SLOAD", storage::sload, schedule.sload, 1, 1);
+    table[0x55] = Instruction::new("SSTORE", storage::sstore, gas::ZERO, 2, 0);
+    table[0x56] = Instruction::new("JUMP", stack::jump, gas::MID, 1, 0);
+    table[0x57] = Instruction::new("JUMPI", stack::jumpi, gas::HIGH, 2, 0);
+    table[0x58] = Instruction::new("PC", stack::pc, gas::BASE, 0, 1);
+    table[0x59] = Instruction::new("MSIZE", memory::msize, gas::BASE, 0, 1);
+    table[0x5a] = Instruction::new("GAS", misc::gas, gas::BASE, 0, 1);
+    table[0x5b] = Instruction::new("JUMPDEST", stack::jumpdest, gas::JUMPDEST, 0, 0);
+
+    if spec.is_enabled_in(Cancun) {
+        table[0x5c] = Instruction::new("TLOAD", storage::tload, gas::WARM_STORAGE_READ, 1, 1);
+        table[0x5d] = Instruction::new("TSTORE", storage::tstore, gas::WARM_STORAGE_READ, 2, 0);
+        table[0x5e] = Instruction::new("MCOPY", memory::mcopy, gas::VERYLOW, 3, 0);
+    }
+    if spec.is_enabled_in(Shanghai) {
+        table[0x5f] = Instruction::new("PUSH0", stack::push0, gas::BASE, 0, 1);
+    }
+    push!(
+        table, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23,
+        24, 25, 26, 27, 28, 29, 30, 31, 32
+    );
+    dup!(table, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16);
+    swap!(table, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16);
+    log!(table, 0, 1, 2, 3, 4);
+
+    table[0xf0] = Instruction::new("CREATE", system::create, gas::CREATE, 3, 1);
+    table[0xf1] = Instruction::new("CALL", system::call, schedule.call, 7, 1);
+    table[0xf2] = Instruction::new("CALLCODE", system::callcode, schedule.call, 7, 1);
+    table[0xf3] = Instruction::new("RETURN", system::return_data, gas::ZERO, 2, 0);
+    if spec.is_enabled_in(Homestead) {
+        table[0xf4] = Instruction::new("DELEGATECALL", system::delegatecall, schedule.call, 6, 1);
+    }
+    if spec.is_enabled_in(Constantinople) {
+        table[0xf5] = Instruction::new("CREATE2", system::create2, gas::CREATE, 4, 1);
+    }
+    if spec.is_enabled_in(Byzantium) {
+        table[0xfa] = Instruction::new("STATICCALL", system::staticcall, schedule.call, 6, 1);
+        table[0xfd] = Instruction::new("REVERT", system::revert, gas::ZERO, 2, 0);
+    }
+    table[0xfe] = Instruction::new("INVALID", misc::invalid, gas::ZERO, 0, 0);
+    table[0xff] = Instruction::new(
+        "SELFDESTRUCT",
+        system::selfdestruct,
+        schedule.selfdestruct,
+        1,
+        0,
+    );
+
+    table
+}
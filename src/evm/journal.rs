@@ -0,0 +1,84 @@
+use std::collections::{HashMap, HashSet};
+
+use primitive_types::{H160, H256, U256};
+
+use super::state::Account;
+use crate::utils::types::{Bytes, Log};
+
+/// A change made to the world state, holding what is needed to undo it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JournalEntry {
+    /// An account was written to while it did not exist
+    AccountTouched {
+        address: H160,
+    },
+    /// An account was deleted at the end of the transaction
+    AccountRemoved {
+        address: H160,
+        account: Account,
+    },
+    BalanceChanged {
+        address: H160,
+        previous: U256,
+    },
+    NonceChanged {
+        address: H160,
+        previous: u64,
+    },
+    CodeChanged {
+        address: H160,
+        previous: Bytes,
+        previous_hash: H256,
+    },
+    StorageChanged {
+        address: H160,
+        key: U256,
+        previous: U256,
+    },
+    TransientStorageChanged {
+        address: H160,
+        key: U256,
+        previous: U256,
+    },
+    /// An address was accessed for the first time in the transaction
+    AddressWarmed {
+        address: H160,
+    },
+    /// A storage slot was accessed for the first time in the transaction
+    SlotWarmed {
+        address: H160,
+        key: U256,
+    },
+    /// A contract was created by the current transaction
+    ContractCreated {
+        address: H160,
+    },
+    /// An account self-destructed
+    AccountDestroyed {
+        address: H160,
+    },
+    /// A log was emitted
+    LogPushed,
+    /// A new transaction started, dropping the records of the previous one
+    TransactionStarted(Box<TransactionRecords>),
+}
+
+/// What the state records about the current transaction only
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransactionRecords {
+    pub created: HashSet<H160>,
+    pub destroyed: HashSet<H160>,
+    pub transient: HashMap<(H160, U256), U256>,
+    pub accessed_addresses: HashSet<H160>,
+    pub accessed_slots: HashSet<(H160, U256)>,
+    pub logs: Vec<Log>,
+}
+
+/// Position in the journal to which the state can be reverted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub(super) index: usize,
+    /// Tells apart every checkpoint taken, on any state, so that one which
+    /// was already closed or taken on another state cannot be reverted to
+    pub(super) id: u64,
+}
@@ -0,0 +1,104 @@
+use primitive_types::U256;
+
+/// The volatile memory of the EVM: a byte array which grows in words
+/// of 32 bytes whenever an instruction accesses a region past its end.
+/// Its length is therefore always the MSIZE of the execution
+#[derive(Debug, Clone, Default)]
+pub struct Memory {
+    data: Vec<u8>,
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory { data: Vec::new() }
+    }
+
+    /// Size of the memory in bytes, always a multiple of 32
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Size the memory would have after accessing `size` bytes at `offset`.
+    /// Accessing zero bytes never expands the memory
+    pub fn expanded_len(&self, offset: usize, size: usize) -> usize {
+        if size == 0 {
+            return self.data.len();
+        }
+        let end = offset + size;
+        self.data.len().max(end.div_ceil(32) * 32)
+    }
+
+    /// Grows the memory so that `size` bytes at `offset` can be accessed
+    pub fn resize(&mut self, offset: usize, size: usize) {
+        let new_len = self.expanded_len(offset, size);
+        if new_len > self.data.len() {
+            self.data.resize(new_len, 0);
+        }
+    }
+
+    /// Reads the word at `offset`. The memory must have already been resized
+    pub fn load(&self, offset: usize) -> U256 {
+        U256::from_big_endian(&self.data[offset..offset + 32])
+    }
+
+    /// Returns `size` bytes at `offset`. The memory must have already been resized
+    pub fn slice(&self, offset: usize, size: usize) -> &[u8] {
+        if size == 0 {
+            return &[];
+        }
+        &self.data[offset..offset + size]
+    }
+
+    /// Writes `value` at `offset`. The memory must have already been resized
+    pub fn store(&mut self, offset: usize, value: &[u8]) {
+        self.data[offset..offset + value.len()].copy_from_slice(value);
+    }
+
+    pub fn store_word(&mut self, offset: usize, value: U256) {
+        let mut bytes = [0u8; 32];
+        value.to_big_endian(&mut bytes);
+        self.store(offset, &bytes);
+    }
+
+    pub fn store_byte(&mut self, offset: usize, value: u8) {
+        self.data[offset] = value;
+    }
+
+    /// Copies `size` bytes at `source_offset` to `offset`, the regions may
+    /// overlap. The memory must have already been resized
+    pub fn copy_within(&mut self, offset: usize, source_offset: usize, size: usize) {
+        if size == 0 {
+            return;
+        }
+        self.data
+            .copy_within(source_offset..source_offset + size, offset);
+    }
+
+    /// Copies `size` bytes of `source` starting at `source_offset` into
+    /// the memory at `offset`. Bytes past the end of `source` are zeros,
+    /// as done by CODECOPY, CALLDATACOPY and EXTCODECOPY
+    pub fn copy_padded(&mut self, offset: usize, source: &[u8], source_offset: usize, size: usize) {
+        if size == 0 {
+            return;
+        }
+        let destination = &mut self.data[offset..offset + size];
+        let available = source.len().saturating_sub(source_offset).min(size);
+        if available > 0 {
+            destination[..available]
+                .copy_from_slice(&source[source_offset..source_offset + available]);
+        }
+        destination[available..].fill(0);
+    }
+}
@@ -1,164 +1,141 @@
-use crate::evm::utils::flip_sign;
-use crate::evm::utils::is_negative;
+use crate::evm::gas::GasSchedule;
+use crate::evm::i256::I256;
 use crate::utils::types::ExecutionData;
 use crate::utils::types::NextAction;
+use crate::utils::types::OpcodeResult;
 use primitive_types::U256;
 
 use crate::evm::EVM;
 
 // 0x01
-pub fn add(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let a = evm.stack.pop().unwrap();
-    let b = evm.stack.pop().unwrap();
+pub fn add(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let a = evm.stack.pop()?;
+    let b = evm.stack.pop()?;
     let (sum, _flag) = a.overflowing_add(b);
-    evm.stack.push(sum);
+    evm.stack.push(sum)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x02
-pub fn mul(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let a = evm.stack.pop().unwrap();
-    let b = evm.stack.pop().unwrap();
+pub fn mul(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let a = evm.stack.pop()?;
+    let b = evm.stack.pop()?;
     let (mul, _flag) = a.overflowing_mul(b);
-    evm.stack.push(mul);
+    evm.stack.push(mul)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x03
-pub fn sub(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let a = evm.stack.pop().unwrap();
-    let b = evm.stack.pop().unwrap();
+pub fn sub(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let a = evm.stack.pop()?;
+    let b = evm.stack.pop()?;
     let (sub, _flag) = a.overflowing_sub(b);
-    evm.stack.push(sub);
+    evm.stack.push(sub)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x04
-pub fn div(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let a = evm.stack.pop().unwrap();
-    let b = evm.stack.pop().unwrap();
+pub fn div(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let a = evm.stack.pop()?;
+    let b = evm.stack.pop()?;
     let zero = U256::zero();
     if b == zero {
-        evm.stack.push(zero);
+        evm.stack.push(zero)?;
     } else {
-        evm.stack.push(a / b);
+        evm.stack.push(a / b)?;
     }
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x05
-pub fn s_div(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let mut a = evm.stack.pop().unwrap();
-    let mut b = evm.stack.pop().unwrap();
+pub fn s_div(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let a = I256(evm.stack.pop()?);
+    let b = I256(evm.stack.pop()?);
+    evm.stack.push(a.signed_div(b).0)?;
 
-    let zero = U256::zero();
-    if b == zero {
-        evm.stack.push(zero);
-        return NextAction::Continue;
-    }
-
-    let is_a_negative = is_negative(&a);
-    let is_b_negative = is_negative(&b);
-
-    // make a and b positive
-    if is_a_negative {
-        flip_sign(&mut a);
-    }
-    if is_b_negative {
-        flip_sign(&mut b);
-    }
-
-    match (is_a_negative, is_b_negative) {
-        (false, false) | (true, true) => evm.stack.push(a / b),
-        _ => {
-            let mut div = a / b;
-            flip_sign(&mut div);
-            evm.stack.push(div);
-        }
-    }
-
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x06
-pub fn modulo(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let a = evm.stack.pop().unwrap();
-    let n = evm.stack.pop().unwrap();
+pub fn modulo(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let a = evm.stack.pop()?;
+    let n = evm.stack.pop()?;
     let zero = U256::zero();
     if n == zero {
-        evm.stack.push(zero);
+        evm.stack.push(zero)?;
     } else {
-        evm.stack.push(a % n);
+        evm.stack.push(a % n)?;
     }
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x07
-pub fn s_modulo(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let mut a = evm.stack.pop().unwrap();
-    let mut n = evm.stack.pop().unwrap();
-
-    let zero = U256::zero();
-    if n == zero {
-        evm.stack.push(zero);
-        return NextAction::Continue;
-    }
-
-    let is_a_negative = is_negative(&a);
-    let is_n_negative = is_negative(&n);
-
-    // Recall that $$ka \equiv kb (\mod n)$$ for any integer $k$
-    if is_a_negative {
-        flip_sign(&mut a);
-    }
-    if is_n_negative {
-        flip_sign(&mut n);
-    }
-
-    let mut result = a % n;
-    match (is_a_negative, is_n_negative) {
-        (false, false) => {
-            evm.stack.push(result);
-        }
-        // Consider an example where a = 10, n = -3 and flip such signs
-        _ => {
-            flip_sign(&mut result);
-            evm.stack.push(result);
-        }
-    }
+/// The result takes the sign of the dividend
+pub fn s_modulo(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let a = I256(evm.stack.pop()?);
+    let n = I256(evm.stack.pop()?);
+    evm.stack.push(a.signed_rem(n).0)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x08
-pub fn add_mod(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    add(evm, _data);
-    modulo(evm, _data);
+pub fn add_mod(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    add(evm, _data)?;
+    modulo(evm, _data)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x09
 /// May have some problems with very big numbers
 /// due to `primitive_types::U256`.
-pub fn mul_mod(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    mul(evm, _data);
-    modulo(evm, _data);
+pub fn mul_mod(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    mul(evm, _data)?;
+    modulo(evm, _data)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0xa
-pub fn exp(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let a = evm.stack.pop().unwrap();
-    let b = evm.stack.pop().unwrap();
+pub fn exp(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let a = evm.stack.pop()?;
+    let b = evm.stack.pop()?;
+
+    // the exponent is charged per byte
+    let exponent_bytes = (b.bits() as u64).div_ceil(8);
+    let byte_cost = GasSchedule::new(evm.spec).exp_byte;
+    evm.gas.record_cost(byte_cost * exponent_bytes)?;
     let (res, _flag) = a.overflowing_pow(b);
-    evm.stack.push(res);
+    evm.stack.push(res)?;
+
+    Ok(NextAction::Continue)
+}
+
+// 0x0b
+/// Extends the sign of the number held in the `b + 1` lowest bytes of `x`
+/// to the whole word
+pub fn sign_extend(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let b = evm.stack.pop()?;
+    let x = evm.stack.pop()?;
+
+    let result = if b < U256::from(31) {
+        let sign_bit = b.as_usize() * 8 + 7;
+        let mask = (U256::one() << sign_bit) - 1;
+        if x.bit(sign_bit) {
+            x | !mask
+        } else {
+            x & mask
+        }
+    } else {
+        x
+    };
+    evm.stack.push(result)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
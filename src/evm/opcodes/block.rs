@@ -1,105 +1,124 @@
-use primitive_types::U256;
+use primitive_types::{H160, H256, U256};
 
 use crate::{
-    evm::EVM,
-    utils::{
-        logger::Logger,
-        types::{ExecutionData, NextAction},
+    evm::{
+        constants::{HISTORY_SERVE_WINDOW, HISTORY_STORAGE_ADDRESS},
+        spec::SpecId,
+        utils::{address_to_u256, u256_to_h256},
+        EVM,
     },
+    utils::types::{ExecutionData, NextAction, OpcodeResult},
 };
 
 // 0x40
-/// Not yet implemented in tests
-pub fn blockhash(_evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    NextAction::Continue
+/// Hash of one of the 256 most recent blocks, zero for any other block.
+/// When enabled from Prague, older hashes are read from the storage of the
+/// history contract (EIP-2935)
+pub fn blockhash(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let number = evm.stack.pop()?;
+
+    let hash = if number > U256::from(u64::MAX) {
+        None
+    } else {
+        let number = number.as_u64();
+        evm.block
+            .block_hash(number)
+            .or_else(|| history_block_hash(evm, number))
+    };
+
+    let val = hash.map_or_else(U256::zero, |hash| U256::from_big_endian(hash.as_bytes()));
+    evm.stack.push(val)?;
+
+    Ok(NextAction::Continue)
 }
 
-// 0x41
-pub fn coinbase(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let coinbase = data.block.as_ref().unwrap().coinbase.as_ref().unwrap();
+/// Hash of block `number` kept by the history contract, which stores
+/// the hashes of the last 8191 blocks in a ring buffer
+fn history_block_hash(evm: &EVM, number: u64) -> Option<H256> {
+    let current = evm.block.number;
+    let enabled = evm.serve_history_hashes && evm.spec.is_enabled_in(SpecId::Prague);
+
+    if !enabled || number >= current || number < current.saturating_sub(HISTORY_SERVE_WINDOW) {
+        return None;
+    }
 
-    let val = U256::from_str_radix(coinbase, 16).unwrap();
-    evm.stack.push(val);
+    let key = U256::from(number % HISTORY_SERVE_WINDOW);
+    let hash = evm
+        .state
+        .storage(&H160::from(HISTORY_STORAGE_ADDRESS), &key);
 
-    NextAction::Continue
+    Some(u256_to_h256(hash))
 }
 
-// 0x42
-pub fn timestamp(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let timestamp = data.block.as_ref().unwrap().timestamp.as_ref().unwrap();
+// 0x41
+pub fn coinbase(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = address_to_u256(&evm.block.coinbase);
+    evm.stack.push(val)?;
 
-    let val = U256::from_str_radix(timestamp, 16).unwrap();
-    evm.stack.push(val);
+    Ok(NextAction::Continue)
+}
+
+// 0x42
+pub fn timestamp(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = U256::from(evm.block.timestamp);
+    evm.stack.push(val)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x43
-pub fn number(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let number = data.block.as_ref().unwrap().number.as_ref().unwrap();
-
-    let val = U256::from_str_radix(number, 16).unwrap();
-    evm.stack.push(val);
+pub fn number(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = U256::from(evm.block.number);
+    evm.stack.push(val)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x44
-pub fn difficulty(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let difficulty = data.block.as_ref().unwrap().difficulty.as_ref().unwrap();
+pub fn difficulty(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = evm.block.difficulty;
+    evm.stack.push(val)?;
 
-    let val = U256::from_str_radix(difficulty, 16).unwrap();
-    evm.stack.push(val);
+    Ok(NextAction::Continue)
+}
+
+// 0x44
+/// Replaces DIFFICULTY since the Merge
+pub fn prevrandao(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = evm.block.prevrandao;
+    evm.stack.push(val)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x45
-pub fn gaslimit(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let gaslimit = data.block.as_ref().unwrap().gaslimit.as_ref().unwrap();
+pub fn gaslimit(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = U256::from(evm.block.gas_limit);
+    evm.stack.push(val)?;
 
-    let val = U256::from_str_radix(gaslimit, 16).unwrap();
-    evm.stack.push(val);
-
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x46
-pub fn chain(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    EVM::debug(&format!("stack {:x?}", evm.stack));
-    let chain_id = data.block.as_ref().unwrap().chainid.as_ref().unwrap();
-
-    let val = U256::from_str_radix(chain_id, 16).unwrap();
-    evm.stack.push(val);
+pub fn chain(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = U256::from(evm.block.chain_id);
+    evm.stack.push(val)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x47
-pub fn selfbalance(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let address = data.tx.as_ref().unwrap().to.as_ref().unwrap();
-    let state = data.state.as_ref().unwrap();
-    let account_state = state.get(address);
-
-    if let None = account_state {
-        evm.stack.push(U256::zero());
-        return NextAction::Continue;
-    }
-
-    let balance = account_state.unwrap().balance.as_ref().unwrap();
-    let balance = U256::from_str_radix(balance, 16).unwrap();
+pub fn selfbalance(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let balance = evm.state.balance(&evm.context.address);
+    evm.stack.push(balance)?;
 
-    evm.stack.push(balance);
-
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x48
-pub fn basefee(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let basefee = data.block.as_ref().unwrap().basefee.as_ref().unwrap();
-
-    let val = U256::from_str_radix(basefee, 16).unwrap();
-    evm.stack.push(val);
+pub fn basefee(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = evm.block.basefee;
+    evm.stack.push(val)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
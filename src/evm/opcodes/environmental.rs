@@ -1,106 +1,85 @@
 use primitive_types::U256;
-use sha3::{Digest, Keccak256};
 
 use crate::{
-    evm::{utils::update_msize, EVM},
-    utils::types::{ExecutionData, NextAction},
+    evm::{
+        gas,
+        utils::{access_account, expand_memory, saturating_usize, u256_to_address},
+        EVM,
+    },
+    utils::types::{EvmError, ExecutionData, NextAction, OpcodeResult},
 };
 
 // 0x3b
-pub fn extcodesize(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let state = data.state;
-    let address = format!("0x{:x}", evm.stack.pop().unwrap());
+pub fn extcodesize(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let address = u256_to_address(evm.stack.pop()?);
+    access_account(evm, &address)?;
 
-    if state.is_none() {
-        evm.stack.push(U256::zero());
-        return NextAction::Continue;
-    };
+    let size = U256::from(evm.state.code(&address).len());
+    evm.stack.push(size)?;
 
-    let account_state = data.state.as_ref().unwrap().get(&address);
+    Ok(NextAction::Continue)
+}
 
-    if account_state.is_none() {
-        evm.stack.push(U256::zero());
-        return NextAction::Continue;
-    };
+// 0x3c
+pub fn extcodecopy(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let address = u256_to_address(evm.stack.pop()?);
+    let dest_offset = evm.stack.pop()?;
+    let offset = saturating_usize(evm.stack.pop()?);
+    let byte_size = evm.stack.pop()?;
+    access_account(evm, &address)?;
 
-    let code = account_state.unwrap().code.as_ref().unwrap().bin.clone();
-    let size = U256::from(code.len() / 2);
+    let (dest_offset, byte_size) = expand_memory(evm, dest_offset, byte_size)?;
+    evm.gas.record_cost(gas::copy_cost(byte_size))?;
 
-    evm.stack.push(size);
+    // a missing account has no code, so only zeros are copied
+    let code = evm.state.code(&address);
 
-    NextAction::Continue
-}
+    evm.memory.copy_padded(dest_offset, code, offset, byte_size);
 
-// 0x3c
-pub fn extcodecopy(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let state = data.state;
-    let address = format!("0x{:x}", evm.stack.pop().unwrap());
-    let dest_offset = evm.stack.pop().unwrap().as_usize();
-    let offset = evm.stack.pop().unwrap().as_usize();
-    let byte_size = evm.stack.pop().unwrap().as_usize();
-
-    if state.is_none() {
-        evm.stack.push(U256::zero());
-        return NextAction::Continue;
-    };
-
-    let account_state = data.state.as_ref().unwrap().get(&address);
-
-    if account_state.is_none() {
-        evm.stack.push(U256::zero());
-        return NextAction::Continue;
-    };
-
-    let code = account_state.unwrap().code.as_ref().unwrap().bin.clone();
-
-    let mut i = 0;
-    while i < byte_size {
-        let range = std::ops::Range::<usize> {
-            start: (offset + i) * 2,
-            end: (offset + i) * 2 + 2,
-        };
-
-        let byte = if let Some(b) = code.get(range) {
-            b
-        } else {
-            "0"
-        };
-
-        let val = u8::from_str_radix(byte, 16).unwrap();
-        evm.memory[dest_offset + i] = val;
-        i = i + 1;
-    }
+    Ok(NextAction::Continue)
+}
 
-    update_msize(evm, dest_offset + byte_size - 1);
+// 0x3d
+pub fn returndatasize(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let size = U256::from(evm.return_data.len());
+    evm.stack.push(size)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
-// 0x3f
-pub fn extcodehash(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let address = format!("0x{:x}", evm.stack.pop().unwrap());
-    let state = data.state;
+// 0x3e
+/// Unlike the other copies, reading past the end of the buffer is an error
+pub fn returndatacopy(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let dest_offset = evm.stack.pop()?;
+    let offset = evm.stack.pop()?;
+    let byte_size = evm.stack.pop()?;
+
+    let end = offset
+        .checked_add(byte_size)
+        .ok_or(EvmError::ReturnDataOutOfBounds)?;
+    if end > U256::from(evm.return_data.len()) {
+        return Err(EvmError::ReturnDataOutOfBounds);
+    }
+    let offset = offset.as_usize();
 
-    if state.is_none() {
-        evm.stack.push(U256::zero());
-        return NextAction::Continue;
-    };
+    let (dest_offset, byte_size) = expand_memory(evm, dest_offset, byte_size)?;
+    evm.gas.record_cost(gas::copy_cost(byte_size))?;
 
-    let account_state = data.state.as_ref().unwrap().get(&address);
+    evm.memory
+        .store(dest_offset, &evm.return_data[offset..offset + byte_size]);
 
-    if account_state.is_none() {
-        evm.stack.push(U256::zero());
-        return NextAction::Continue;
-    };
+    Ok(NextAction::Continue)
+}
 
-    let code = account_state.unwrap().code.as_ref().unwrap().bin.clone();
+// 0x3f
+pub fn extcodehash(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let address = u256_to_address(evm.stack.pop()?);
+    access_account(evm, &address)?;
 
-    let mut hasher = Keccak256::new();
-    hasher.update(code);
-    let hash = hasher.finalize().to_vec();
+    let hash = evm.state.code_hash(&address);
 
-    let val = U256::from(&hash[..]);
-    evm.stack.push(val);
+    let val = U256::from_big_endian(hash.as_bytes());
+    evm.stack.push(val)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
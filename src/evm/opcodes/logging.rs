@@ -0,0 +1,39 @@
+use crate::{
+    evm::{
+        gas,
+        utils::{expand_memory, u256_to_h256},
+        EVM,
+    },
+    utils::types::{EvmError, ExecutionData, Log, NextAction, OpcodeResult},
+};
+
+// 0xa0..0xa4
+pub fn log_n<const N: usize>(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    if evm.context.is_static {
+        return Err(EvmError::StaticCallViolation);
+    }
+
+    let offset = evm.stack.pop()?;
+    let size = evm.stack.pop()?;
+
+    evm.gas.record_cost(gas::LOG_TOPIC * N as u64)?;
+    let (offset, size) = expand_memory(evm, offset, size)?;
+    evm.gas.record_cost(gas::LOG_DATA * size as u64)?;
+
+    let data = evm.memory.slice(offset, size).to_vec();
+
+    let mut topics = Vec::with_capacity(N);
+
+    for _i in 0..N {
+        let topic = evm.stack.pop()?;
+        topics.push(u256_to_h256(topic));
+    }
+
+    evm.state.push_log(Log {
+        address: evm.context.address,
+        topics,
+        data,
+    });
+
+    Ok(NextAction::Continue)
+}
@@ -1,64 +1,67 @@
 use primitive_types::U256;
 
 use crate::{
-    evm::{utils::update_msize, EVM},
-    utils::types::{NextAction, ExecutionData},
+    evm::{gas, utils::expand_memory, EVM},
+    utils::types::{ExecutionData, NextAction, OpcodeResult},
 };
 
 // 0x51
-pub fn mload(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let offset = evm.stack.pop().unwrap().as_usize();
+pub fn mload(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let offset = evm.stack.pop()?;
 
-    let mut str = String::with_capacity(32);
+    let (offset, _) = expand_memory(evm, offset, U256::from(32))?;
 
-    // to change for load
-    for i in 0..=31 {
-        let byte = evm.memory[offset + i];
-        if byte <= u8::from(15) {
-            str.push_str(&format!("0{:x}", byte));
-        } else {
-            str.push_str(&format!("{:x}", byte));
-        }
-    }
+    let val = evm.memory.load(offset);
+    evm.stack.push(val)?;
 
-    let val = U256::from_str_radix(&str, 16).unwrap();
-    evm.stack.push(val);
-
-    update_msize(evm, offset + 31);
-
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x52
-pub fn mstore(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let offset = evm.stack.pop().unwrap().as_usize();
-    let val = evm.stack.pop().unwrap();
+pub fn mstore(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let offset = evm.stack.pop()?;
+    let val = evm.stack.pop()?;
 
-    for i in 0..=31 {
-        evm.memory[offset + 31 - i] = val.byte(i);
-    }
+    let (offset, _) = expand_memory(evm, offset, U256::from(32))?;
 
-    update_msize(evm, offset + 31);
+    evm.memory.store_word(offset, val);
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x53
-pub fn mstore8(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let offset = evm.stack.pop().unwrap().as_usize();
-    let val = evm.stack.pop().unwrap();
+pub fn mstore8(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let offset = evm.stack.pop()?;
+    let val = evm.stack.pop()?;
 
-    evm.memory[offset] = u8::try_from(val % 256).ok().unwrap();
+    let (offset, _) = expand_memory(evm, offset, U256::one())?;
 
-    update_msize(evm, offset);
+    evm.memory.store_byte(offset, val.byte(0));
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x59
-pub fn msize(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let val = U256::from(evm.msize);
-    evm.stack.push(val);
+pub fn msize(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = U256::from(evm.memory.len());
+    evm.stack.push(val)?;
+
+    Ok(NextAction::Continue)
+}
+
+// 0x5e
+/// Copies a region of memory to another, which may overlap (EIP-5656).
+/// The memory grows to fit the farthest of the two regions
+pub fn mcopy(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let dest_offset = evm.stack.pop()?;
+    let offset = evm.stack.pop()?;
+    let size = evm.stack.pop()?;
+
+    let (offset, _) = expand_memory(evm, offset, size)?;
+    let (dest_offset, size) = expand_memory(evm, dest_offset, size)?;
+    evm.gas.record_cost(gas::copy_cost(size))?;
+
+    evm.memory.copy_within(dest_offset, offset, size);
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
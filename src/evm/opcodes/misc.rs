@@ -1,137 +1,143 @@
 use primitive_types::U256;
-use sha3::{Digest, Keccak256};
 
 use crate::{
     evm::{
-        utils::{flip_sign, is_negative, update_msize},
+        gas,
+        i256::I256,
+        utils::{expand_memory, keccak256, saturating_usize},
         EVM,
     },
     utils::{
         logger::Logger,
-        types::{ExecutionData, NextAction},
+        types::{EvmError, ExecutionData, ExitReason, NextAction, OpcodeResult},
     },
 };
 
 // 0x00
-pub fn stop(_evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    NextAction::Exit(0)
+pub fn stop(_evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    Ok(NextAction::Exit(ExitReason::Stop))
 }
 
 // 0x1b
-pub fn shl(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let shift = evm.stack.pop().unwrap();
-    let val = evm.stack.pop().unwrap();
-    evm.stack.push(val << shift);
+/// Shifting by 256 bits or more gives zero
+pub fn shl(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let shift = evm.stack.pop()?;
+    let val = evm.stack.pop()?;
 
-    NextAction::Continue
+    let result = if shift >= U256::from(256) {
+        U256::zero()
+    } else {
+        val << shift
+    };
+    evm.stack.push(result)?;
+
+    Ok(NextAction::Continue)
 }
 
 // 0x1c
-pub fn shr(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let shift = evm.stack.pop().unwrap();
-    let val = evm.stack.pop().unwrap();
-    evm.stack.push(val >> shift);
+/// Shifting by 256 bits or more gives zero
+pub fn shr(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let shift = evm.stack.pop()?;
+    let val = evm.stack.pop()?;
+
+    let result = if shift >= U256::from(256) {
+        U256::zero()
+    } else {
+        val >> shift
+    };
+    evm.stack.push(result)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x1d
-/// This does not work if the number is small and shift is greater or equal 8
-pub fn sar(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let shift = evm.stack.pop().unwrap();
-    let mut val = evm.stack.pop().unwrap();
-
-    if is_negative(&val) {
-        flip_sign(&mut val);
-        let mut result = val >> shift;
-        flip_sign(&mut result);
-        evm.stack.push(result);
-    } else {
-        evm.stack.push(val >> shift);
-    }
+pub fn sar(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let shift = evm.stack.pop()?;
+    let val = I256(evm.stack.pop()?);
+    evm.stack.push(val.arithmetic_shr(shift).0)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x1a
-pub fn byte(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let offset = evm.stack.pop().unwrap();
-    let val = evm.stack.pop().unwrap();
+pub fn byte(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let offset = evm.stack.pop()?;
+    let val = evm.stack.pop()?;
 
     if offset >= U256::from(32) {
         EVM::warning("byte offset greater or equal than 32");
-        evm.stack.push(U256::zero());
-        return NextAction::Continue;
+        evm.stack.push(U256::zero())?;
+        return Ok(NextAction::Continue);
     }
 
     let byte_offset = 31 - usize::from(offset.byte(0));
 
     let result = val.byte(byte_offset);
-    evm.stack.push(U256::from(result));
+    evm.stack.push(U256::from(result))?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x20
-pub fn sha3(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let mut hasher = Keccak256::new();
-
-    let starting_offset = evm.stack.pop().unwrap().as_usize();
-    let ending_offset = evm.stack.pop().unwrap().as_usize();
+pub fn sha3(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let offset = evm.stack.pop()?;
+    let size = evm.stack.pop()?;
 
-    let data = &evm.memory[starting_offset..ending_offset];
-    hasher.update(data);
+    let (offset, size) = expand_memory(evm, offset, size)?;
+    evm.gas.record_cost(gas::SHA3_WORD * gas::num_words(size))?;
 
-    let hash = hasher.finalize();
-    let hash_vec = hash.to_vec();
+    let hash = keccak256(evm.memory.slice(offset, size));
 
-    let val = U256::from(&hash_vec[..]);
-    evm.stack.push(val);
+    let val = U256::from(hash.as_bytes());
+    evm.stack.push(val)?;
 
-    update_msize(evm, ending_offset);
-
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x38
-pub fn codesize(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let size = data.bytecode.len();
+pub fn codesize(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let size = evm.code.len();
 
-    evm.stack.push(U256::from(size));
+    evm.stack.push(U256::from(size))?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x39
-pub fn codecopy(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let dest_offset = evm.stack.pop().unwrap().as_usize();
-    let offset = evm.stack.pop().unwrap().as_usize();
-    let size = evm.stack.pop().unwrap().as_usize();
-
-    let mut i = 0;
-    while i < size {
-        let byte = data
-            .bytecode
-            .get(offset + i)
-            .map(|b| b.clone())
-            .unwrap_or(0);
-        evm.memory[dest_offset + i] = byte;
-        i = i + 1;
-    }
+pub fn codecopy(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let dest_offset = evm.stack.pop()?;
+    let offset = saturating_usize(evm.stack.pop()?);
+    let size = evm.stack.pop()?;
+
+    let (dest_offset, size) = expand_memory(evm, dest_offset, size)?;
+    evm.gas.record_cost(gas::copy_cost(size))?;
 
-    update_msize(evm, dest_offset + size - 1);
+    let code = evm.code.clone();
+    evm.memory
+        .copy_padded(dest_offset, code.code(), offset, size);
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x5a
-/// This is not supported yet, it returns `U256::MAX`
-pub fn gas(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    evm.stack.push(U256::MAX);
-    NextAction::Continue
+/// Returns `U256::MAX` when the execution is not metered
+pub fn gas(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let remaining = if evm.gas.is_unlimited() {
+        U256::MAX
+    } else {
+        U256::from(evm.gas.remaining())
+    };
+    evm.stack.push(remaining)?;
+    Ok(NextAction::Continue)
 }
 
 // 0xfe
-pub fn invalid(_evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    NextAction::Exit(1)
+pub fn invalid(_evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    Err(EvmError::InvalidOpcode(0xfe))
+}
+
+/// Handler of every byte which is not an instruction
+pub fn unknown(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let opcode = evm.code.padded()[evm.pc - 1];
+    Err(EvmError::InvalidOpcode(opcode))
 }
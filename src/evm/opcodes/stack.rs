@@ -1,56 +1,88 @@
 use primitive_types::U256;
 
 use crate::{
-    evm::{utils::is_pc_on_jumpdest, EVM},
-    utils::types::{NextAction, ExecutionData},
+    evm::{utils::to_usize, EVM},
+    utils::types::{EvmError, ExecutionData, NextAction, OpcodeResult},
 };
 
 // 0x50
-pub fn pop(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    evm.stack.pop();
-    NextAction::Continue
+pub fn pop(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    evm.stack.pop()?;
+    Ok(NextAction::Continue)
 }
 
 // 0x56
-pub fn jump(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let new_pc = evm.stack.pop().unwrap().as_usize();
+pub fn jump(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let new_pc = evm.stack.pop()?;
+    let new_pc = to_usize(new_pc, EvmError::BadJumpDestination)?;
 
     evm.pc = new_pc;
 
-    if is_pc_on_jumpdest(evm) {
-        NextAction::Continue
+    if evm.code.is_jumpdest(evm.pc) {
+        Ok(NextAction::Continue)
     } else {
-        // TODO: maybe another status code for this?
-        NextAction::Exit(1)
+        Err(EvmError::BadJumpDestination)
     }
 }
 
 // 0x57
-pub fn jumpi(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let new_pc = evm.stack.pop().unwrap().as_usize();
-    let condition = evm.stack.pop().unwrap();
+pub fn jumpi(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let new_pc = evm.stack.pop()?;
+    let condition = evm.stack.pop()?;
 
-    if !condition.is_zero() {
-        evm.pc = new_pc;
-    } else {
-        return NextAction::Continue;
+    // the destination only matters when the jump is taken
+    if condition.is_zero() {
+        return Ok(NextAction::Continue);
     }
+    evm.pc = to_usize(new_pc, EvmError::BadJumpDestination)?;
 
-    if is_pc_on_jumpdest(evm) {
-        NextAction::Continue
+    if evm.code.is_jumpdest(evm.pc) {
+        Ok(NextAction::Continue)
     } else {
-        // TODO: maybe another status code for this?
-        NextAction::Exit(1)
+        Err(EvmError::BadJumpDestination)
     }
 }
 
 // 0x58
-pub fn pc(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    evm.stack.push(U256::from(evm.pc - 1));
-    NextAction::Continue
+pub fn pc(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    evm.stack.push(U256::from(evm.pc - 1))?;
+    Ok(NextAction::Continue)
 }
 
 // 0x5B
-pub fn jumpdest(_evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    NextAction::Continue
+pub fn jumpdest(_evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    Ok(NextAction::Continue)
+}
+
+// 0x5f
+pub fn push0(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    evm.stack.push(U256::zero())?;
+
+    Ok(NextAction::Continue)
+}
+
+// 0x60..0x7f
+/// The immediate is read big-endian. An immediate cut by the end of the
+/// code is completed with zeros on the right, as the code is padded
+pub fn push_n<const N: usize>(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let immediate = &evm.code.padded()[evm.pc..evm.pc + N];
+    let num = U256::from_big_endian(immediate);
+    evm.pc += N;
+    evm.stack.push(num)?;
+
+    Ok(NextAction::Continue)
+}
+
+// 0x80..0x8f
+pub fn dup_n<const N: usize>(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    evm.stack.dup(N)?;
+
+    Ok(NextAction::Continue)
+}
+
+// 0x90..0x9f
+pub fn swap_n<const N: usize>(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    evm.stack.swap(N)?;
+
+    Ok(NextAction::Continue)
 }
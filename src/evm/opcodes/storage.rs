@@ -1,29 +1,141 @@
 use primitive_types::U256;
 
 use crate::{
-    evm::EVM,
-    utils::types::{ExecutionData, NextAction},
+    evm::{
+        gas::{self, GasSchedule},
+        spec::SpecId,
+        state::StorageSlot,
+        EVM,
+    },
+    utils::types::{EvmError, ExecutionData, NextAction, OpcodeResult},
 };
 
 // 0x54
-pub fn sload(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let key = evm.stack.pop().unwrap().as_usize();
-    let value = if let Some(v) = evm.storage.get(&key) {
-        v.clone()
+pub fn sload(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let key = evm.stack.pop()?;
+
+    let cold = evm.state.warm_slot(&evm.context.address, key);
+    if cold && evm.spec.is_enabled_in(SpecId::Berlin) {
+        evm.gas
+            .record_cost(gas::COLD_SLOAD - gas::WARM_STORAGE_READ)?;
+    }
+
+    let value = evm.state.storage(&evm.context.address, &key);
+    evm.stack.push(value)?;
+
+    Ok(NextAction::Continue)
+}
+
+// 0x55
+/// Priced from the original, present and new values of the slot since
+/// Istanbul (EIP-2200), so that writes which are undone within the
+/// transaction are mostly refunded. Constantinople had the same metering
+/// without the stipend check (EIP-1283), which Petersburg took back
+pub fn sstore(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    if evm.context.is_static {
+        return Err(EvmError::StaticCallViolation);
+    }
+
+    let key = evm.stack.pop()?;
+    let val = evm.stack.pop()?;
+
+    let schedule = GasSchedule::new(evm.spec);
+    let eip2200 = evm.spec.is_enabled_in(SpecId::Istanbul);
+    let net_metering = eip2200 || evm.spec == SpecId::Constantinople;
+
+    // a call given only the stipend must not write to storage
+    if eip2200 && evm.gas.remaining() <= gas::CALL_STIPEND {
+        return Err(EvmError::OutOfGas);
+    }
+
+    let cold = evm.state.warm_slot(&evm.context.address, key);
+    if cold && evm.spec.is_enabled_in(SpecId::Berlin) {
+        evm.gas.record_cost(gas::COLD_SLOAD)?;
+    }
+
+    let slot = evm.state.storage_slot(&evm.context.address, &key);
+    let (cost, refund) = if net_metering {
+        sstore_cost(&schedule, slot, val)
+    } else if slot.present.is_zero() && !val.is_zero() {
+        (gas::SSTORE_SET, 0)
+    } else if !slot.present.is_zero() && val.is_zero() {
+        (schedule.sstore_reset, schedule.sstore_clears_refund)
     } else {
-        U256::zero()
+        (schedule.sstore_reset, 0)
     };
-    evm.stack.push(value);
 
-    NextAction::Continue
+    evm.gas.record_cost(cost)?;
+    evm.gas.record_refund(refund);
+
+    evm.state.set_storage(&evm.context.address, key, val);
+
+    Ok(NextAction::Continue)
 }
 
-// 0x55
-pub fn sstore(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let key = evm.stack.pop().unwrap().as_usize();
-    let val = evm.stack.pop().unwrap();
+/// Cost and refund of writing `new` to `slot` (EIP-2200). The refund may
+/// be negative when it takes back one given by an earlier write
+fn sstore_cost(schedule: &GasSchedule, slot: StorageSlot, new: U256) -> (u64, i64) {
+    let StorageSlot { original, present } = slot;
+
+    if present == new {
+        return (schedule.sload, 0);
+    }
+
+    // first write of the transaction to the slot
+    if original == present {
+        return if original.is_zero() {
+            (gas::SSTORE_SET, 0)
+        } else if new.is_zero() {
+            (schedule.sstore_reset, schedule.sstore_clears_refund)
+        } else {
+            (schedule.sstore_reset, 0)
+        };
+    }
+
+    // the slot is dirty, its write was already paid for
+    let mut refund = 0;
+    if !original.is_zero() {
+        if present.is_zero() {
+            refund -= schedule.sstore_clears_refund;
+        } else if new.is_zero() {
+            refund += schedule.sstore_clears_refund;
+        }
+    }
+    if original == new {
+        let paid = if original.is_zero() {
+            gas::SSTORE_SET
+        } else {
+            schedule.sstore_reset
+        };
+        refund += (paid - schedule.sload) as i64;
+    }
+
+    (schedule.sload, refund)
+}
+
+// 0x5c
+/// Reads the transient storage of the account (EIP-1153)
+pub fn tload(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let key = evm.stack.pop()?;
+    let value = evm.state.transient_storage(&evm.context.address, &key);
+    evm.stack.push(value)?;
+
+    Ok(NextAction::Continue)
+}
+
+// 0x5d
+/// Writes to the transient storage of the account, which is dropped at
+/// the end of the transaction (EIP-1153)
+pub fn tstore(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    if evm.context.is_static {
+        return Err(EvmError::StaticCallViolation);
+    }
+
+    let key = evm.stack.pop()?;
+    let val = evm.stack.pop()?;
 
-    evm.storage.insert(key, val);
+    evm.state
+        .set_transient_storage(&evm.context.address, key, val);
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
@@ -1,28 +1,301 @@
+use primitive_types::{H160, U256};
+
 use crate::{
-    evm::{EVM, utils::update_msize},
-    utils::types::{ExecutionData, NextAction},
+    evm::{
+        constants::MAX_INITCODE_SIZE,
+        frame::{CallContext, CallKind, CallOutcome, CreateScheme, ReturnTarget, Transfer},
+        gas,
+        spec::SpecId,
+        utils::{access_account, address_to_u256, expand_memory, u256_to_address, u256_to_h256},
+        EVM,
+    },
+    utils::types::{EvmError, ExecutionData, ExitReason, NextAction, OpcodeResult},
 };
 
-// 0xf3
-pub fn return_data(evm: &mut EVM, _data: &ExecutionData) -> NextAction {
-    let offset = evm.stack.pop().unwrap().as_usize();
-    let size = evm.stack.pop().unwrap().as_usize();
-
-    let mut str = String::with_capacity(size);
-
-    // to change for load
-    for i in 0..=size - 1 {
-        let byte = evm.memory[offset + i];
-        if byte <= u8::from(15) {
-            str.push_str(&format!("0{:x}", byte));
-        } else {
-            str.push_str(&format!("{:x}", byte));
+/// Shared implementation of the message calls, which only differ
+/// in their arguments and in the context given to the callee
+fn message_call(evm: &mut EVM, kind: CallKind) -> OpcodeResult {
+    let gas_requested = evm.stack.pop()?;
+    let address = u256_to_address(evm.stack.pop()?);
+    let value = match kind {
+        CallKind::Call | CallKind::CallCode => evm.stack.pop()?,
+        CallKind::DelegateCall | CallKind::StaticCall => U256::zero(),
+    };
+    let args_offset = evm.stack.pop()?;
+    let args_size = evm.stack.pop()?;
+    let ret_offset = evm.stack.pop()?;
+    let ret_size = evm.stack.pop()?;
+
+    let transfers_value = !value.is_zero();
+
+    if kind == CallKind::Call && transfers_value && evm.context.is_static {
+        return Err(EvmError::StaticCallViolation);
+    }
+
+    access_account(evm, &address)?;
+    let (args_offset, args_size) = expand_memory(evm, args_offset, args_size)?;
+    let (ret_offset, ret_size) = expand_memory(evm, ret_offset, ret_size)?;
+
+    if transfers_value {
+        evm.gas.record_cost(gas::CALL_VALUE)?;
+    }
+
+    // before EIP-161, calling a missing account costs even without value
+    let creates_account = if evm.spec.is_enabled_in(SpecId::SpuriousDragon) {
+        transfers_value && evm.state.is_dead(&address)
+    } else {
+        !evm.state.exists(&address)
+    };
+    if kind == CallKind::Call && creates_account {
+        evm.gas.record_cost(gas::NEW_ACCOUNT)?;
+    }
+
+    // the callee gets a free stipend when receiving value
+    let stipend = if transfers_value {
+        gas::CALL_STIPEND
+    } else {
+        0
+    };
+    let child_gas = evm.gas.forward(gas_requested, stipend, evm.spec)?;
+
+    let input = evm.memory.slice(args_offset, args_size).to_vec();
+    let parent = &evm.context;
+
+    let context = match kind {
+        CallKind::Call => CallContext {
+            address,
+            caller: parent.address,
+            code_address: address,
+            value,
+            input,
+            is_static: parent.is_static,
+        },
+        // runs the code of the target on the current account
+        CallKind::CallCode => CallContext {
+            address: parent.address,
+            caller: parent.address,
+            code_address: address,
+            value,
+            input,
+            is_static: parent.is_static,
+        },
+        // like CALLCODE, but keeps the caller and the value of the current call
+        CallKind::DelegateCall => CallContext {
+            address: parent.address,
+            caller: parent.caller,
+            code_address: address,
+            value: parent.value,
+            input,
+            is_static: parent.is_static,
+        },
+        CallKind::StaticCall => CallContext {
+            address,
+            caller: parent.address,
+            code_address: address,
+            value,
+            input,
+            is_static: true,
+        },
+    };
+
+    let transfer = transfers_value.then_some(Transfer {
+        from: parent.address,
+        to: context.address,
+        value,
+    });
+
+    let target = ReturnTarget::Call {
+        ret_offset,
+        ret_size,
+    };
+
+    evm.call(context, child_gas, transfer, target)
+}
+
+/// Resumes the caller of a message call, once the callee is over
+pub fn finish_call(
+    evm: &mut EVM,
+    outcome: CallOutcome,
+    ret_offset: usize,
+    ret_size: usize,
+) -> OpcodeResult {
+    evm.gas.reclaim(&outcome.gas);
+    if outcome.reason.is_success() {
+        evm.gas.record_refund(outcome.gas.refunded());
+    }
+
+    let copy_size = ret_size.min(outcome.output.len());
+    evm.memory.store(ret_offset, &outcome.output[..copy_size]);
+
+    evm.return_data = outcome.output;
+    evm.stack
+        .push(U256::from(u8::from(outcome.reason.is_success())))?;
+
+    Ok(NextAction::Continue)
+}
+
+/// Shared implementation of CREATE and CREATE2
+fn create_contract(evm: &mut EVM, is_create2: bool) -> OpcodeResult {
+    if evm.context.is_static {
+        return Err(EvmError::StaticCallViolation);
+    }
+
+    let value = evm.stack.pop()?;
+    let offset = evm.stack.pop()?;
+    let size = evm.stack.pop()?;
+    let scheme = if is_create2 {
+        CreateScheme::Create2 {
+            salt: u256_to_h256(evm.stack.pop()?),
+        }
+    } else {
+        CreateScheme::Create
+    };
+
+    let (offset, size) = expand_memory(evm, offset, size)?;
+
+    // EIP-3860
+    if evm.spec.is_enabled_in(SpecId::Shanghai) {
+        if size > MAX_INITCODE_SIZE {
+            return Err(EvmError::InitCodeSizeLimit);
         }
+        evm.gas
+            .record_cost(gas::INITCODE_WORD * gas::num_words(size))?;
+    }
+    if is_create2 {
+        // the init code is hashed to derive the address
+        evm.gas.record_cost(gas::SHA3_WORD * gas::num_words(size))?;
+    }
+
+    // the init code gets all the gas it can
+    let child_gas = evm
+        .gas
+        .forward(U256::from(evm.gas.remaining()), 0, evm.spec)?;
+    let init_code = evm.memory.slice(offset, size).to_vec();
+
+    evm.create(scheme, value, &init_code, child_gas)
+}
+
+/// Resumes the creator of a contract, once its init code is over
+pub fn finish_create(evm: &mut EVM, outcome: CallOutcome, address: H160) -> OpcodeResult {
+    evm.gas.reclaim(&outcome.gas);
+
+    // only a reverted creation leaves data to return
+    evm.return_data = match outcome.reason {
+        ExitReason::Revert => outcome.output,
+        _ => Vec::new(),
+    };
+
+    if outcome.reason.is_success() {
+        evm.gas.record_refund(outcome.gas.refunded());
+        evm.stack.push(address_to_u256(&address))?;
+    } else {
+        evm.stack.push(U256::zero())?;
     }
 
-    evm.result = str;
+    Ok(NextAction::Continue)
+}
+
+// 0xf0
+pub fn create(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    create_contract(evm, false)
+}
+
+// 0xf1
+pub fn call(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    message_call(evm, CallKind::Call)
+}
+
+// 0xf2
+pub fn callcode(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    message_call(evm, CallKind::CallCode)
+}
+
+// 0xf3
+pub fn return_data(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let offset = evm.stack.pop()?;
+    let size = evm.stack.pop()?;
+
+    let (offset, size) = expand_memory(evm, offset, size)?;
+
+    evm.result = evm.memory.slice(offset, size).to_vec();
+
+    Ok(NextAction::Exit(ExitReason::Return))
+}
+
+// 0xf4
+pub fn delegatecall(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    message_call(evm, CallKind::DelegateCall)
+}
+
+// 0xf5
+pub fn create2(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    create_contract(evm, true)
+}
+
+// 0xfa
+pub fn staticcall(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    message_call(evm, CallKind::StaticCall)
+}
 
-    update_msize(evm, offset + size - 1);
+// 0xfd
+/// Like RETURN, but the execution fails and its state changes are discarded
+pub fn revert(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let offset = evm.stack.pop()?;
+    let size = evm.stack.pop()?;
+
+    let (offset, size) = expand_memory(evm, offset, size)?;
+
+    evm.result = evm.memory.slice(offset, size).to_vec();
+
+    Ok(NextAction::Exit(ExitReason::Revert))
+}
+
+// 0xff
+/// Sends the whole balance of the account to the beneficiary. Since Cancun
+/// (EIP-6780) the account is only deleted if it was created by the same
+/// transaction; before, it is always deleted at the end of the transaction
+pub fn selfdestruct(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    if evm.context.is_static {
+        return Err(EvmError::StaticCallViolation);
+    }
+
+    let beneficiary = u256_to_address(evm.stack.pop()?);
+    let address = evm.context.address;
+    let balance = evm.state.balance(&address);
+
+    // the whole cold cost, as the static gas is not an access cost
+    if evm.state.warm_address(&beneficiary) && evm.spec.is_enabled_in(SpecId::Berlin) {
+        evm.gas.record_cost(gas::COLD_ACCOUNT_ACCESS)?;
+    }
+
+    // charged since EIP-150, and only when sending value since EIP-161
+    let creates_account = if evm.spec.is_enabled_in(SpecId::SpuriousDragon) {
+        !balance.is_zero() && evm.state.is_dead(&beneficiary)
+    } else {
+        !evm.state.exists(&beneficiary)
+    };
+    if evm.spec.is_enabled_in(SpecId::TangerineWhistle) && creates_account {
+        evm.gas.record_cost(gas::NEW_ACCOUNT)?;
+    }
+
+    let destroys = !evm.spec.is_enabled_in(SpecId::Cancun) || evm.state.is_created(&address);
+
+    // a destroyed account burns what it sends to itself
+    if beneficiary != address || destroys {
+        evm.state.set_balance(&address, U256::zero());
+    }
+    if beneficiary != address {
+        let beneficiary_balance = evm.state.balance(&beneficiary);
+        evm.state
+            .set_balance(&beneficiary, beneficiary_balance.saturating_add(balance));
+    }
+
+    if destroys {
+        let refunds = !evm.spec.is_enabled_in(SpecId::London);
+        if evm.state.mark_destroyed(&address) && refunds {
+            evm.gas.record_refund(gas::SELFDESTRUCT_REFUND);
+        }
+    }
 
-    NextAction::Continue
+    Ok(NextAction::Exit(ExitReason::Stop))
 }
@@ -1,120 +1,110 @@
 use primitive_types::U256;
 
 use crate::{
-    evm::{utils::update_msize, EVM},
-    utils::types::{ExecutionData, NextAction},
+    evm::{
+        gas,
+        utils::{address_to_u256, expand_memory, saturating_usize},
+        EVM,
+    },
+    utils::types::{ExecutionData, NextAction, OpcodeResult},
 };
 
 // 0x30
-pub fn address(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let to_address = data.tx.as_ref().unwrap().to.as_ref().unwrap();
+pub fn address(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = address_to_u256(&evm.context.address);
+    evm.stack.push(val)?;
 
-    let val = U256::from_str_radix(to_address, 16).unwrap();
-    evm.stack.push(val);
-
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x32
-pub fn origin(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let origin = data.tx.as_ref().unwrap().origin.as_ref().unwrap();
-
-    let val = U256::from_str_radix(origin, 16).unwrap();
-    evm.stack.push(val);
+pub fn origin(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = address_to_u256(&evm.tx.origin);
+    evm.stack.push(val)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x33
-pub fn caller(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let from_address = data.tx.as_ref().unwrap().from.as_ref().unwrap();
+pub fn caller(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = address_to_u256(&evm.context.caller);
+    evm.stack.push(val)?;
 
-    let val = U256::from_str_radix(from_address, 16).unwrap();
-    evm.stack.push(val);
-
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x34
-pub fn callvalue(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let value = data.tx.as_ref().unwrap().value.as_ref().unwrap();
-
-    let val = U256::from_str_radix(value, 16).unwrap();
-    evm.stack.push(val);
+pub fn callvalue(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = evm.context.value;
+    evm.stack.push(val)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x35
-pub fn calldataload(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let offset = evm.stack.pop().unwrap().as_usize();
-    let data = data.tx.as_ref().unwrap().data.as_ref().unwrap();
-    let mut parsed_data = String::with_capacity(32);
-
-    for i in 0..=63 {
-        let j = i + offset * 2;
-        if let Some(v) = data.get(j..j + 1) {
-            parsed_data.push_str(v)
-        } else {
-            parsed_data.push_str("0")
-        };
+pub fn calldataload(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let offset = evm.stack.pop()?;
+    // past the end of the calldata everything reads as zero
+    let offset = saturating_usize(offset);
+    let calldata = &evm.context.input;
+
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = offset
+            .checked_add(i)
+            .and_then(|index| calldata.get(index))
+            .copied()
+            .unwrap_or(0);
     }
 
-    let val = U256::from_str_radix(&parsed_data, 16).unwrap();
-    evm.stack.push(val);
+    let val = U256::from_big_endian(&bytes);
+    evm.stack.push(val)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x36
-pub fn calldatasize(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    if data.tx.is_none() {
-        evm.stack.push(U256::zero());
-        return NextAction::Continue;
-    }
-
-    let data = data.tx.as_ref().unwrap().data.as_ref().unwrap();
-
-    // data.len is even, every two chars is a byte
-    let size = data.len() / 2;
+pub fn calldatasize(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = U256::from(evm.context.input.len());
+    evm.stack.push(val)?;
 
-    let val = U256::from(size);
-    evm.stack.push(val);
-
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x37
-pub fn calldatacopy(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let dest_offset = evm.stack.pop().unwrap().as_usize();
-    let offset = evm.stack.pop().unwrap().as_usize();
-    let byte_size = evm.stack.pop().unwrap().as_usize();
-
-    let str_data = data.tx.as_ref().unwrap().data.as_ref().unwrap();
-
-    let mut i = 0;
-    while i < byte_size {
-        let range = std::ops::Range::<usize> {
-            start: (offset + i) * 2,
-            end: (offset + i) * 2 + 2,
-        };
-        let byte = str_data.get(range).unwrap();
-        let val = u8::from_str_radix(byte, 16).unwrap();
-        evm.memory[dest_offset + i] = val;
-        i = i + 1;
-    }
+pub fn calldatacopy(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let dest_offset = evm.stack.pop()?;
+    let offset = saturating_usize(evm.stack.pop()?);
+    let byte_size = evm.stack.pop()?;
+
+    let (dest_offset, byte_size) = expand_memory(evm, dest_offset, byte_size)?;
+    evm.gas.record_cost(gas::copy_cost(byte_size))?;
 
-    update_msize(evm, dest_offset + byte_size - 1);
+    evm.memory
+        .copy_padded(dest_offset, &evm.context.input, offset, byte_size);
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
 
 // 0x3a
-pub fn gasprice(evm: &mut EVM, data: &ExecutionData) -> NextAction {
-    let gasprice = data.tx.as_ref().unwrap().gasprice.as_ref().unwrap();
+pub fn gasprice(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let val = evm.tx.gas_price;
+    evm.stack.push(val)?;
+
+    Ok(NextAction::Continue)
+}
+
+// 0x49
+/// Versioned hash of a blob of the transaction, zero past the last one
+pub fn blobhash(evm: &mut EVM, _data: &ExecutionData) -> OpcodeResult {
+    let index = evm.stack.pop()?;
 
-    let val = U256::from_str_radix(gasprice, 16).unwrap();
-    evm.stack.push(val);
+    let hash = match usize::try_from(index) {
+        Ok(index) => evm.tx.blob_hashes.get(index).copied(),
+        Err(_) => None,
+    };
+    let val = hash.map_or_else(U256::zero, |hash| U256::from_big_endian(hash.as_bytes()));
+    evm.stack.push(val)?;
 
-    NextAction::Continue
+    Ok(NextAction::Continue)
 }
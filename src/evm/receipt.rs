@@ -0,0 +1,103 @@
+use primitive_types::H160;
+
+use super::utils::keccak256;
+use crate::utils::types::{EvmResult, Log};
+
+/// Size of a logs bloom in bytes
+pub const BLOOM_SIZE: usize = 256;
+
+/// 2048-bit bloom filter of the addresses and topics of logs, as found in
+/// receipts and block headers
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Bloom(pub [u8; BLOOM_SIZE]);
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Bloom([0; BLOOM_SIZE])
+    }
+}
+
+impl std::fmt::Debug for Bloom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{}", hex::encode(self.0))
+    }
+}
+
+impl Bloom {
+    pub fn new() -> Bloom {
+        Bloom::default()
+    }
+
+    /// Bloom of the addresses and topics of `logs`
+    pub fn from_logs(logs: &[Log]) -> Bloom {
+        let mut bloom = Bloom::new();
+        for log in logs {
+            bloom.accrue_log(log);
+        }
+        bloom
+    }
+
+    /// Bits set for `input`: the low 11 bits of each of the first three
+    /// pairs of bytes of its hash
+    fn bits(input: &[u8]) -> [usize; 3] {
+        let hash = keccak256(input);
+        let hash = hash.as_bytes();
+
+        [0, 2, 4].map(|i| (usize::from(hash[i]) << 8 | usize::from(hash[i + 1])) & 0x7ff)
+    }
+
+    pub fn accrue(&mut self, input: &[u8]) {
+        for bit in Bloom::bits(input) {
+            self.0[BLOOM_SIZE - 1 - bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    pub fn accrue_log(&mut self, log: &Log) {
+        self.accrue(log.address.as_bytes());
+        for topic in &log.topics {
+            self.accrue(topic.as_bytes());
+        }
+    }
+
+    pub fn accrue_bloom(&mut self, other: &Bloom) {
+        for (byte, other) in self.0.iter_mut().zip(other.0) {
+            *byte |= other;
+        }
+    }
+
+    /// Whether `input` may have been accrued. False positives are possible,
+    /// false negatives are not
+    pub fn contains_input(&self, input: &[u8]) -> bool {
+        Bloom::bits(input)
+            .iter()
+            .all(|bit| self.0[BLOOM_SIZE - 1 - bit / 8] & (1 << (bit % 8)) != 0)
+    }
+
+    pub fn contains_address(&self, address: &H160) -> bool {
+        self.contains_input(address.as_bytes())
+    }
+}
+
+/// Receipt of a transaction, as stored by a node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    /// Whether the transaction succeeded (EIP-658)
+    pub status: bool,
+    /// Gas used by this transaction and the ones before it in the block
+    pub cumulative_gas_used: u64,
+    pub logs: Vec<Log>,
+    pub logs_bloom: Bloom,
+}
+
+impl Receipt {
+    /// Receipt of the execution, following transactions of the same block
+    /// which used `previous_cumulative_gas` gas
+    pub fn new(result: &EvmResult, previous_cumulative_gas: u64) -> Receipt {
+        Receipt {
+            status: result.success,
+            cumulative_gas_used: previous_cumulative_gas.saturating_add(result.gas_used),
+            logs: result.logs.clone(),
+            logs_bloom: Bloom::from_logs(&result.logs),
+        }
+    }
+}
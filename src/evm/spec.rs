@@ -0,0 +1,52 @@
+use serde::Deserialize;
+
+/// Hardforks of the Ethereum mainnet, in activation order.
+///
+/// Forks which did not change the behaviour of the interpreter, such as
+/// the DAO fork or those which only delayed the difficulty bomb, are left out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Deserialize)]
+pub enum SpecId {
+    Frontier,
+    Homestead,
+    TangerineWhistle,
+    SpuriousDragon,
+    Byzantium,
+    Constantinople,
+    Petersburg,
+    Istanbul,
+    Berlin,
+    London,
+    Merge,
+    Shanghai,
+    #[default]
+    Cancun,
+    Prague,
+}
+
+impl SpecId {
+    /// Whether the rules introduced by `fork` apply
+    pub const fn is_enabled_in(self, fork: SpecId) -> bool {
+        self as u8 >= fork as u8
+    }
+
+    /// Number of precompiled contracts, which live at the addresses
+    /// starting from 0x01
+    pub const fn precompile_count(self) -> u64 {
+        match self {
+            SpecId::Frontier
+            | SpecId::Homestead
+            | SpecId::TangerineWhistle
+            | SpecId::SpuriousDragon => 4,
+            SpecId::Byzantium | SpecId::Constantinople | SpecId::Petersburg => 8,
+            SpecId::Istanbul
+            | SpecId::Berlin
+            | SpecId::London
+            | SpecId::Merge
+            | SpecId::Shanghai => 9,
+            // the point evaluation of EIP-4844
+            SpecId::Cancun => 10,
+            // the BLS12-381 operations of EIP-2537
+            SpecId::Prague => 17,
+        }
+    }
+}
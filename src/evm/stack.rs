@@ -0,0 +1,85 @@
+use primitive_types::U256;
+
+use super::constants::STACK_LIMIT;
+use crate::utils::types::EvmError;
+
+/// The EVM stack: at most `STACK_LIMIT` words of 256 bits.
+/// Every operation is checked, so that a malformed contract results
+/// in an exceptional halt instead of a panic
+#[derive(Debug, Clone, Default)]
+pub struct Stack {
+    data: Vec<U256>,
+}
+
+impl Stack {
+    pub fn new() -> Stack {
+        Stack {
+            data: Vec::with_capacity(STACK_LIMIT),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+
+    /// Items of the stack, from the bottom to the top
+    pub fn data(&self) -> &[U256] {
+        &self.data
+    }
+
+    pub fn push(&mut self, value: U256) -> Result<(), EvmError> {
+        if self.data.len() >= STACK_LIMIT {
+            return Err(EvmError::StackOverflow);
+        }
+        self.data.push(value);
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Result<U256, EvmError> {
+        self.data.pop().ok_or(EvmError::StackUnderflow)
+    }
+
+    /// Returns the `n`-th item from the top of the stack, where 0 is the top
+    pub fn peek(&self, n: usize) -> Result<U256, EvmError> {
+        if n >= self.data.len() {
+            return Err(EvmError::StackUnderflow);
+        }
+        Ok(self.data[self.data.len() - 1 - n])
+    }
+
+    /// Duplicates the `n`-th item of the stack, where 1 is the top
+    pub fn dup(&mut self, n: usize) -> Result<(), EvmError> {
+        let value = self.peek(n - 1)?;
+        self.push(value)
+    }
+
+    /// Swaps the top of the stack with the `n+1`-th item
+    pub fn swap(&mut self, n: usize) -> Result<(), EvmError> {
+        if n >= self.data.len() {
+            return Err(EvmError::StackUnderflow);
+        }
+        let top = self.data.len() - 1;
+        self.data.swap(top, top - n);
+        Ok(())
+    }
+
+    /// Checks that an instruction which pops `inputs` items and pushes
+    /// `outputs` items can be executed on the current stack
+    pub fn require(&self, inputs: usize, outputs: usize) -> Result<(), EvmError> {
+        if self.data.len() < inputs {
+            return Err(EvmError::StackUnderflow);
+        }
+        if self.data.len() - inputs + outputs > STACK_LIMIT {
+            return Err(EvmError::StackOverflow);
+        }
+        Ok(())
+    }
+}
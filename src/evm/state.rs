@@ -0,0 +1,570 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use primitive_types::{H160, H256, U256};
+
+use super::{
+    constants::KECCAK_EMPTY,
+    journal::{Checkpoint, JournalEntry, TransactionRecords},
+    utils::{keccak256, parse_hex_field, u256_to_address},
+};
+use crate::utils::types::{AccountState, Bytes, EvmError, Log, State};
+
+/// Value of a storage slot, along with its value at the start of the
+/// transaction, which SSTORE needs to price the write
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageSlot {
+    pub original: U256,
+    pub present: U256,
+}
+
+impl StorageSlot {
+    pub fn is_changed(&self) -> bool {
+        self.original != self.present
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Account {
+    pub nonce: u64,
+    pub balance: U256,
+    pub code: Bytes,
+    /// Hash of `code`, kept along with it so that calls do not hash it
+    pub code_hash: H256,
+    pub storage: HashMap<U256, StorageSlot>,
+}
+
+impl Default for Account {
+    fn default() -> Self {
+        Account {
+            nonce: 0,
+            balance: U256::zero(),
+            code: Bytes::new(),
+            code_hash: H256(KECCAK_EMPTY),
+            storage: HashMap::new(),
+        }
+    }
+}
+
+impl Account {
+    /// An account is empty when it has no code, no nonce and no balance
+    /// (EIP-161)
+    pub fn is_empty(&self) -> bool {
+        self.nonce == 0 && self.balance.is_zero() && self.code.is_empty()
+    }
+
+    fn from_fixture(account: &AccountState) -> Result<Account, EvmError> {
+        let nonce = match account.nonce.as_ref() {
+            Some(_) => parse_hex_field(account.nonce.as_ref(), "state.nonce")?,
+            None => U256::zero(),
+        };
+        if nonce > U256::from(u64::MAX) {
+            return Err(EvmError::InvalidHex("state.nonce"));
+        }
+
+        let balance = match account.balance.as_ref() {
+            Some(_) => parse_hex_field(account.balance.as_ref(), "state.balance")?,
+            None => U256::zero(),
+        };
+
+        let code = match account.code.as_ref() {
+            Some(code) => hex::decode(&code.bin).map_err(|_| EvmError::InvalidHex("state.code"))?,
+            None => Bytes::new(),
+        };
+
+        let mut storage = HashMap::new();
+        for (key, value) in account.storage.iter().flatten() {
+            let key = parse_hex_field(Some(key), "state.storage.key")?;
+            let value = parse_hex_field(Some(value), "state.storage.value")?;
+            storage.insert(
+                key,
+                StorageSlot {
+                    original: value,
+                    present: value,
+                },
+            );
+        }
+
+        Ok(Account {
+            nonce: nonce.as_u64(),
+            balance,
+            code_hash: keccak256(&code),
+            code,
+            storage,
+        })
+    }
+}
+
+/// Id of the next checkpoint taken on any state
+static NEXT_CHECKPOINT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Accounts of the chain, by address. Reading a missing account gives the
+/// values of an empty one, and writing to it creates it.
+///
+/// Every change made during a transaction is recorded in a journal, so that
+/// it can be undone back to a checkpoint, as done when a call fails
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorldState {
+    accounts: HashMap<H160, Account>,
+    /// Contracts created by the current transaction
+    created: HashSet<H160>,
+    /// Accounts to delete at the end of the current transaction
+    destroyed: HashSet<H160>,
+    /// Storage which only lives for the current transaction (EIP-1153).
+    /// Zero values are not kept
+    transient: HashMap<(H160, U256), U256>,
+    /// Addresses and storage slots accessed by the current transaction,
+    /// which are warm (EIP-2929)
+    accessed_addresses: HashSet<H160>,
+    accessed_slots: HashSet<(H160, U256)>,
+    logs: Vec<Log>,
+    journal: Vec<JournalEntry>,
+    /// Checkpoints neither reverted nor committed, the innermost last
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl WorldState {
+    pub fn new() -> WorldState {
+        WorldState::default()
+    }
+
+    /// Parses the state of a test fixture, failing on the first
+    /// malformed address or field
+    pub fn from_fixture(state: &State) -> Result<WorldState, EvmError> {
+        let mut accounts = HashMap::with_capacity(state.len());
+
+        for (address, account) in state {
+            let address = parse_hex_field(Some(address), "state.address")?;
+            if address.bits() > 160 {
+                return Err(EvmError::InvalidHex("state.address"));
+            }
+
+            accounts.insert(u256_to_address(address), Account::from_fixture(account)?);
+        }
+
+        Ok(WorldState {
+            accounts,
+            ..WorldState::default()
+        })
+    }
+
+    /// Writes the accounts of a test fixture over the ones of this state.
+    /// The slots of the storage missing from the fixture are kept
+    pub fn apply_fixture(&mut self, state: &State) -> Result<(), EvmError> {
+        let fixture = WorldState::from_fixture(state)?;
+
+        for (address, account) in fixture.accounts {
+            self.set_balance(&address, account.balance);
+            self.set_nonce(&address, account.nonce);
+            self.set_code(&address, account.code);
+            for (key, slot) in account.storage {
+                self.set_storage(&address, key, slot.present);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Marks the current position of the journal. Every checkpoint must be
+    /// closed by either `revert_to` or `commit`, innermost first.
+    ///
+    /// A checkpoint stays open across transactions, so that the changes
+    /// of several of them can be undone at once
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        let checkpoint = Checkpoint {
+            index: self.journal.len(),
+            // unique across states, so that one cannot match another's
+            id: NEXT_CHECKPOINT_ID.fetch_add(1, Ordering::Relaxed),
+        };
+        self.checkpoints.push(checkpoint);
+
+        checkpoint
+    }
+
+    /// Undoes every change made since `checkpoint`, and closes it.
+    /// Fails if `checkpoint` is not the innermost open one of this state
+    pub fn revert_to(&mut self, checkpoint: Checkpoint) -> Result<(), EvmError> {
+        if self.checkpoints.last() != Some(&checkpoint) {
+            return Err(EvmError::StaleCheckpoint);
+        }
+        self.checkpoints.pop();
+
+        while self.journal.len() > checkpoint.index {
+            if let Some(entry) = self.journal.pop() {
+                self.undo(entry);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keeps the changes made since the last checkpoint, and closes it.
+    /// Once no checkpoint is left, the changes cannot be undone anymore
+    pub fn commit(&mut self) {
+        self.checkpoints.pop();
+        if self.checkpoints.is_empty() {
+            self.journal.clear();
+        }
+    }
+
+    fn undo(&mut self, entry: JournalEntry) {
+        match entry {
+            JournalEntry::AccountTouched { address } => {
+                self.accounts.remove(&address);
+            }
+            JournalEntry::AccountRemoved { address, account } => {
+                self.accounts.insert(address, account);
+            }
+            JournalEntry::BalanceChanged { address, previous } => {
+                self.account_entry(&address).balance = previous;
+            }
+            JournalEntry::NonceChanged { address, previous } => {
+                self.account_entry(&address).nonce = previous;
+            }
+            JournalEntry::CodeChanged {
+                address,
+                previous,
+                previous_hash,
+            } => {
+                let account = self.account_entry(&address);
+                account.code = previous;
+                account.code_hash = previous_hash;
+            }
+            JournalEntry::StorageChanged {
+                address,
+                key,
+                previous,
+            } => {
+                self.account_entry(&address)
+                    .storage
+                    .entry(key)
+                    .or_default()
+                    .present = previous;
+            }
+            JournalEntry::TransientStorageChanged {
+                address,
+                key,
+                previous,
+            } => {
+                self.write_transient(address, key, previous);
+            }
+            JournalEntry::AddressWarmed { address } => {
+                self.accessed_addresses.remove(&address);
+            }
+            JournalEntry::SlotWarmed { address, key } => {
+                self.accessed_slots.remove(&(address, key));
+            }
+            JournalEntry::ContractCreated { address } => {
+                self.created.remove(&address);
+            }
+            JournalEntry::AccountDestroyed { address } => {
+                self.destroyed.remove(&address);
+            }
+            JournalEntry::LogPushed => {
+                self.logs.pop();
+            }
+            JournalEntry::TransactionStarted(records) => {
+                let TransactionRecords {
+                    created,
+                    destroyed,
+                    transient,
+                    accessed_addresses,
+                    accessed_slots,
+                    logs,
+                } = *records;
+                self.created = created;
+                self.destroyed = destroyed;
+                self.transient = transient;
+                self.accessed_addresses = accessed_addresses;
+                self.accessed_slots = accessed_slots;
+                self.logs = logs;
+            }
+        }
+    }
+
+    pub fn exists(&self, address: &H160) -> bool {
+        self.accounts.contains_key(address)
+    }
+
+    /// Whether an account is missing or empty, which are equivalent
+    /// since EIP-161
+    pub fn is_dead(&self, address: &H160) -> bool {
+        self.account(address).is_none_or(Account::is_empty)
+    }
+
+    pub fn account(&self, address: &H160) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+
+    fn account_entry(&mut self, address: &H160) -> &mut Account {
+        self.accounts.entry(*address).or_default()
+    }
+
+    /// Account at `address`, which is created empty if it does not exist.
+    /// The creation is journaled, but not the changes made to the account
+    fn account_mut(&mut self, address: &H160) -> &mut Account {
+        if !self.accounts.contains_key(address) {
+            self.journal
+                .push(JournalEntry::AccountTouched { address: *address });
+        }
+        self.account_entry(address)
+    }
+
+    pub fn balance(&self, address: &H160) -> U256 {
+        self.account(address)
+            .map(|account| account.balance)
+            .unwrap_or_default()
+    }
+
+    pub fn set_balance(&mut self, address: &H160, balance: U256) {
+        let account = self.account_mut(address);
+        let previous = mem::replace(&mut account.balance, balance);
+        self.journal.push(JournalEntry::BalanceChanged {
+            address: *address,
+            previous,
+        });
+    }
+
+    pub fn nonce(&self, address: &H160) -> u64 {
+        self.account(address)
+            .map(|account| account.nonce)
+            .unwrap_or_default()
+    }
+
+    pub fn set_nonce(&mut self, address: &H160, nonce: u64) {
+        let account = self.account_mut(address);
+        let previous = mem::replace(&mut account.nonce, nonce);
+        self.journal.push(JournalEntry::NonceChanged {
+            address: *address,
+            previous,
+        });
+    }
+
+    pub fn code(&self, address: &H160) -> &[u8] {
+        self.account(address)
+            .map(|account| account.code.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn set_code(&mut self, address: &H160, code: Bytes) {
+        let hash = keccak256(&code);
+        let account = self.account_mut(address);
+        let previous = mem::replace(&mut account.code, code);
+        let previous_hash = mem::replace(&mut account.code_hash, hash);
+        self.journal.push(JournalEntry::CodeChanged {
+            address: *address,
+            previous,
+            previous_hash,
+        });
+    }
+
+    /// Code of an account along with its hash, which is not computed again
+    pub fn code_with_hash(&self, address: &H160) -> (&[u8], H256) {
+        match self.account(address) {
+            Some(account) => (&account.code, account.code_hash),
+            None => (&[], H256(KECCAK_EMPTY)),
+        }
+    }
+
+    /// Hash of the code of an account, zero if the account does not exist
+    /// or is empty (EIP-1052)
+    pub fn code_hash(&self, address: &H160) -> H256 {
+        match self.account(address) {
+            Some(account) if !account.is_empty() => account.code_hash,
+            _ => H256::zero(),
+        }
+    }
+
+    /// Slot `key` of the storage of an account. Slots never written
+    /// read as zero
+    pub fn storage_slot(&self, address: &H160, key: &U256) -> StorageSlot {
+        self.account(address)
+            .and_then(|account| account.storage.get(key))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn storage(&self, address: &H160, key: &U256) -> U256 {
+        self.storage_slot(address, key).present
+    }
+
+    pub fn set_storage(&mut self, address: &H160, key: U256, value: U256) {
+        let slot = self.account_mut(address).storage.entry(key).or_default();
+        let previous = mem::replace(&mut slot.present, value);
+        self.journal.push(JournalEntry::StorageChanged {
+            address: *address,
+            key,
+            previous,
+        });
+    }
+
+    pub fn transient_storage(&self, address: &H160, key: &U256) -> U256 {
+        self.transient
+            .get(&(*address, *key))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_transient_storage(&mut self, address: &H160, key: U256, value: U256) {
+        let previous = self.write_transient(*address, key, value);
+        self.journal.push(JournalEntry::TransientStorageChanged {
+            address: *address,
+            key,
+            previous,
+        });
+    }
+
+    /// Writes a transient slot, returning its previous value
+    fn write_transient(&mut self, address: H160, key: U256, value: U256) -> U256 {
+        let previous = if value.is_zero() {
+            self.transient.remove(&(address, key))
+        } else {
+            self.transient.insert((address, key), value)
+        };
+        previous.unwrap_or_default()
+    }
+
+    pub fn is_address_warm(&self, address: &H160) -> bool {
+        self.accessed_addresses.contains(address)
+    }
+
+    /// Marks an address as accessed, returning whether it was cold
+    pub fn warm_address(&mut self, address: &H160) -> bool {
+        let cold = self.accessed_addresses.insert(*address);
+        if cold {
+            self.journal
+                .push(JournalEntry::AddressWarmed { address: *address });
+        }
+        cold
+    }
+
+    pub fn is_slot_warm(&self, address: &H160, key: &U256) -> bool {
+        self.accessed_slots.contains(&(*address, *key))
+    }
+
+    /// Marks a storage slot as accessed, returning whether it was cold
+    pub fn warm_slot(&mut self, address: &H160, key: U256) -> bool {
+        let cold = self.accessed_slots.insert((*address, key));
+        if cold {
+            self.journal.push(JournalEntry::SlotWarmed {
+                address: *address,
+                key,
+            });
+        }
+        cold
+    }
+
+    pub fn is_created(&self, address: &H160) -> bool {
+        self.created.contains(address)
+    }
+
+    pub fn mark_created(&mut self, address: &H160) {
+        if self.created.insert(*address) {
+            self.journal
+                .push(JournalEntry::ContractCreated { address: *address });
+        }
+    }
+
+    /// Schedules the deletion of an account at the end of the transaction,
+    /// returning whether it was not already scheduled
+    pub fn mark_destroyed(&mut self, address: &H160) -> bool {
+        let inserted = self.destroyed.insert(*address);
+        if inserted {
+            self.journal
+                .push(JournalEntry::AccountDestroyed { address: *address });
+        }
+        inserted
+    }
+
+    pub fn logs(&self) -> &[Log] {
+        &self.logs
+    }
+
+    pub fn push_log(&mut self, log: Log) {
+        self.logs.push(log);
+        self.journal.push(JournalEntry::LogPushed);
+    }
+
+    /// Starts a new transaction: the present value of every storage slot
+    /// becomes its original value, and what was recorded for the previous
+    /// transaction is dropped.
+    ///
+    /// While a checkpoint is open, what was recorded is journaled, so that
+    /// reverting across transactions brings back the logs and the other
+    /// records of the earlier one. The original values of the slots are
+    /// not restored, the next transaction sets them again anyway
+    pub fn start_transaction(&mut self) {
+        for account in self.accounts.values_mut() {
+            account.storage.retain(|_, slot| !slot.present.is_zero());
+            for slot in account.storage.values_mut() {
+                slot.original = slot.present;
+            }
+        }
+
+        let records = TransactionRecords {
+            created: mem::take(&mut self.created),
+            destroyed: mem::take(&mut self.destroyed),
+            transient: mem::take(&mut self.transient),
+            accessed_addresses: mem::take(&mut self.accessed_addresses),
+            accessed_slots: mem::take(&mut self.accessed_slots),
+            logs: mem::take(&mut self.logs),
+        };
+
+        // the changes stay undoable while a checkpoint is open
+        if self.checkpoints.is_empty() {
+            self.journal.clear();
+        } else {
+            self.journal
+                .push(JournalEntry::TransactionStarted(Box::new(records)));
+        }
+    }
+
+    /// Ends the transaction by deleting the accounts which self-destructed,
+    /// and dropping the transient storage
+    pub fn finish_transaction(&mut self) {
+        self.transient.clear();
+
+        let destroyed: Vec<H160> = self.destroyed.iter().copied().collect();
+
+        for address in destroyed {
+            if let Some(account) = self.accounts.remove(&address) {
+                self.journal
+                    .push(JournalEntry::AccountRemoved { address, account });
+            }
+        }
+    }
+
+    /// Moves the storage of every account of `other` into this state, slot
+    /// by slot. The slots this state already holds keep their value.
+    /// Not journaled
+    pub fn take_storage(&mut self, other: WorldState) {
+        for (address, account) in other.accounts {
+            if account.storage.is_empty() {
+                continue;
+            }
+
+            let storage = &mut self.account_entry(&address).storage;
+            for (key, slot) in account.storage {
+                storage.entry(key).or_insert(slot);
+            }
+        }
+    }
+
+    /// Moves `value` from one account to the other, failing if the
+    /// sender cannot afford it
+    pub fn transfer(&mut self, from: &H160, to: &H160, value: U256) -> Result<(), EvmError> {
+        let from_balance = self.balance(from);
+        if from_balance < value {
+            return Err(EvmError::InsufficientBalance);
+        }
+        self.set_balance(from, from_balance - value);
+
+        let to_balance = self.balance(to);
+        self.set_balance(to, to_balance.saturating_add(value));
+
+        Ok(())
+    }
+}
@@ -1,310 +1,127 @@
-use std::collections::HashMap;
-
-use crate::{
-    evm::{opcodes, EVM},
-    utils::{
-        logger::Logger,
-        types::{ExecutionData, Logs, NextAction, Opcode, Opcodes},
-    },
-};
-use primitive_types::U256;
-
-use super::constants::JUMPDEST;
-
-/// Flips the sign of a number using two's complement
-pub fn flip_sign(num: &mut U256) {
-    *num = !*num + 1;
-}
-
-/// Check if the given number is negative according to
-/// its binary representation, looking at the MSB
-pub fn is_negative(num: &U256) -> bool {
-    num.bit(255)
-}
-
-fn push_n(evm: &mut EVM, n: u8, data: &ExecutionData) -> NextAction {
-    let mut str = String::new();
-    for _i in 1..=n {
-        let byte = data.bytecode.get(evm.pc).expect("Missing data");
-        if byte <= &u8::from(15) {
-            str.push_str(&format!("0{:x}", byte));
-        } else {
-            str.push_str(&format!("{:x}", byte));
+use crate::{evm::gas, evm::EVM, utils::types::EvmError};
+use primitive_types::{H160, H256, U256};
+use sha3::{Digest, Keccak256};
+
+use super::{constants::MEMORY_LIMIT, spec::SpecId};
+
+pub fn keccak256(data: &[u8]) -> H256 {
+    H256::from_slice(&Keccak256::digest(data))
+}
+
+/// Address of a contract made by CREATE: the hash of the RLP encoding
+/// of the list `[creator, nonce]`
+pub fn create_address(creator: &H160, nonce: u64) -> H160 {
+    let nonce_bytes = nonce.to_be_bytes();
+    let nonce_bytes = &nonce_bytes[nonce_bytes.iter().take_while(|b| **b == 0).count()..];
+
+    // the payload always fits the short forms of the encoding
+    let mut payload = Vec::with_capacity(30);
+    payload.push(0x80 + 20);
+    payload.extend_from_slice(creator.as_bytes());
+    match nonce_bytes {
+        [byte] if *byte < 0x80 => payload.push(*byte),
+        _ => {
+            payload.push(0x80 + nonce_bytes.len() as u8);
+            payload.extend_from_slice(nonce_bytes);
         }
-        evm.pc += 1;
     }
-    let num = U256::from_str_radix(&str, 16).unwrap();
-    evm.stack.push(num);
 
-    NextAction::Continue
-}
+    let mut rlp = Vec::with_capacity(payload.len() + 1);
+    rlp.push(0xc0 + payload.len() as u8);
+    rlp.extend_from_slice(&payload);
 
-fn generate_push_n_fn(n: u8) -> Opcode {
-    if n > 32 {
-        panic!("ERROR: arg must be a number between 0 and 32 included")
-    }
-
-    Box::new(move |evm: &mut EVM, _data: &ExecutionData| push_n(evm, n, _data))
+    H160::from_slice(&keccak256(&rlp)[12..])
 }
 
-fn dup_n(evm: &mut EVM, n: u8) -> NextAction {
-    let mut temp_stack: Vec<U256> = Vec::with_capacity(usize::from(n));
-
-    // pop until we find the value to duplicate
-    for _i in 1..n {
-        let val = evm.stack.pop().unwrap();
-        temp_stack.push(val);
-    }
-
-    let val_to_dup = evm.stack.pop().unwrap();
-    evm.stack.push(val_to_dup.clone());
+/// Address of a contract made by CREATE2 (EIP-1014)
+pub fn create2_address(creator: &H160, salt: &H256, init_code: &[u8]) -> H160 {
+    let mut preimage = Vec::with_capacity(85);
+    preimage.push(0xff);
+    preimage.extend_from_slice(creator.as_bytes());
+    preimage.extend_from_slice(salt.as_bytes());
+    preimage.extend_from_slice(keccak256(init_code).as_bytes());
 
-    // fill the stack back
-    for _i in 1..n {
-        let val = temp_stack.pop().unwrap();
-        evm.stack.push(val);
-    }
-
-    evm.stack.push(val_to_dup);
-
-    NextAction::Continue
+    H160::from_slice(&keccak256(&preimage)[12..])
 }
 
-fn generate_dup_n_fn(n: u8) -> Opcode {
-    if n == 0 || n > 16 {
-        EVM::error("Invalid N value for DUP N: it must be between 0 and 16 excluded");
-        panic!();
-    }
-
-    Box::new(move |evm: &mut EVM, _data: &ExecutionData| dup_n(evm, n))
+/// Parses an optional hex field of the transaction or of the block,
+/// failing with the name of the field if it is missing or malformed
+pub fn parse_hex_field(value: Option<&String>, field: &'static str) -> Result<U256, EvmError> {
+    let value = value.ok_or(EvmError::MissingField(field))?;
+    U256::from_str_radix(value, 16).map_err(|_| EvmError::InvalidHex(field))
 }
 
-/// todo this is not good yet
-fn swap_n(evm: &mut EVM, n: u8) -> NextAction {
-    let mut temp_stack: Vec<U256> = Vec::with_capacity(usize::from(n));
-    let first_val = evm.stack.pop().unwrap();
-
-    // pop until we find the value to swap
-    for _i in 1..n {
-        let val = evm.stack.pop().unwrap();
-        temp_stack.push(val);
-    }
-
-    let last_val = evm.stack.pop().unwrap();
-
-    evm.stack.push(first_val);
-
-    // fill the stack back
-    for _i in 1..n {
-        let val = temp_stack.pop().unwrap();
-        evm.stack.push(val);
-    }
-
-    evm.stack.push(last_val);
-
-    NextAction::Continue
+/// Takes the 20 low-order bytes of a stack value as an address
+pub fn u256_to_address(value: U256) -> H160 {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    H160::from_slice(&bytes[12..])
 }
 
-fn generate_swap_n_fn(n: u8) -> Opcode {
-    if n == 0 || n > 16 {
-        EVM::error("Invalid N value for SWAP N: it must be between 0 and 16 excluded");
-        panic!();
-    }
-
-    Box::new(move |evm: &mut EVM, _data: &ExecutionData| swap_n(evm, n))
+pub fn u256_to_h256(value: U256) -> H256 {
+    let mut bytes = [0u8; 32];
+    value.to_big_endian(&mut bytes);
+    H256::from(bytes)
 }
 
-fn log_n(evm: &mut EVM, data: &ExecutionData, n: u8) -> NextAction {
-    if n > 5 {
-        panic!("Invalid number for LOG0...LOG4 operation");
-    }
-
-    let address = data.tx.as_ref().unwrap().to.as_ref().unwrap();
-
-    let offset = evm.stack.pop().unwrap().as_usize();
-    let size = evm.stack.pop().unwrap().as_usize();
-
-    let mut str = String::with_capacity(32);
-
-    for i in 0..=size {
-        let byte = evm.memory[offset + i];
-        if byte <= u8::from(15) {
-            str.push_str(&format!("0{:x}", byte));
-        } else {
-            str.push_str(&format!("{:x}", byte));
-        }
-    }
-
-    update_msize(evm, offset + size);
-
-    let mut topics: Vec<String> = Vec::with_capacity(4);
-
-    if n >= 1 {
-        let mut counter = 1;
-        while counter <= n {
-            topics.push(format!("{:x?}", evm.stack.pop().unwrap()));
-            counter += 1;
-        }
-    }
-
-    evm.logs = Logs {
-        address: address.clone(),
-        data: str.clone(),
-        topics,
-    };
-
-    NextAction::Continue
+pub fn address_to_u256(address: &H160) -> U256 {
+    U256::from_big_endian(address.as_bytes())
 }
 
-fn generate_log_n_fn(n: u8) -> Opcode {
-    Box::new(move |evm: &mut EVM, data: &ExecutionData| log_n(evm, data, n))
+/// Converts a stack value to a `usize`, clamping it to `usize::MAX`.
+/// Used for offsets in read-only buffers, which read as zeros past their end
+pub fn saturating_usize(value: U256) -> usize {
+    value.min(U256::from(usize::MAX)).as_usize()
 }
 
-// It'd better to have them static. See PHF crate.
-pub fn get_opcodes() -> Opcodes {
-    let mut opcodes: Opcodes = HashMap::new();
-
-    opcodes.insert(0x00, Box::new(opcodes::misc::stop));
-    opcodes.insert(0x01, Box::new(opcodes::arithmetic::add));
-    opcodes.insert(0x02, Box::new(opcodes::arithmetic::mul));
-    opcodes.insert(0x03, Box::new(opcodes::arithmetic::sub));
-    opcodes.insert(0x04, Box::new(opcodes::arithmetic::div));
-    opcodes.insert(0x05, Box::new(opcodes::arithmetic::s_div));
-    opcodes.insert(0x06, Box::new(opcodes::arithmetic::modulo));
-    opcodes.insert(0x07, Box::new(opcodes::arithmetic::s_modulo));
-    opcodes.insert(0x08, Box::new(opcodes::arithmetic::add_mod));
-    opcodes.insert(0x09, Box::new(opcodes::arithmetic::mul_mod));
-    opcodes.insert(0x0a, Box::new(opcodes::arithmetic::exp));
-
-    opcodes.insert(0x10, Box::new(opcodes::logic::lt));
-    opcodes.insert(0x11, Box::new(opcodes::logic::gt));
-    opcodes.insert(0x12, Box::new(opcodes::logic::slt));
-    opcodes.insert(0x13, Box::new(opcodes::logic::sgt));
-    opcodes.insert(0x14, Box::new(opcodes::logic::eq));
-    opcodes.insert(0x15, Box::new(opcodes::logic::is_zero));
-    opcodes.insert(0x16, Box::new(opcodes::logic::and));
-    opcodes.insert(0x17, Box::new(opcodes::logic::or));
-    opcodes.insert(0x18, Box::new(opcodes::logic::xor));
-    opcodes.insert(0x19, Box::new(opcodes::logic::not));
-
-    opcodes.insert(0x1b, Box::new(opcodes::misc::shl));
-    opcodes.insert(0x1c, Box::new(opcodes::misc::shr));
-    opcodes.insert(0x1d, Box::new(opcodes::misc::sar));
-    opcodes.insert(0x1a, Box::new(opcodes::misc::byte));
-    opcodes.insert(0x20, Box::new(opcodes::misc::sha3));
-
-    opcodes.insert(0x30, Box::new(opcodes::transaction::address));
-    opcodes.insert(0x31, Box::new(opcodes::state::balance));
-    opcodes.insert(0x32, Box::new(opcodes::transaction::origin));
-    opcodes.insert(0x33, Box::new(opcodes::transaction::caller));
-    opcodes.insert(0x34, Box::new(opcodes::transaction::callvalue));
-    opcodes.insert(0x35, Box::new(opcodes::transaction::calldataload));
-    opcodes.insert(0x36, Box::new(opcodes::transaction::calldatasize));
-    opcodes.insert(0x37, Box::new(opcodes::transaction::calldatacopy));
-    opcodes.insert(0x38, Box::new(opcodes::misc::codesize));
-    opcodes.insert(0x39, Box::new(opcodes::misc::codecopy));
-    opcodes.insert(0x3a, Box::new(opcodes::transaction::gasprice));
-    opcodes.insert(0x3b, Box::new(opcodes::environmental::extcodesize));
-    opcodes.insert(0x3c, Box::new(opcodes::environmental::extcodecopy));
-    opcodes.insert(0x3f, Box::new(opcodes::environmental::extcodehash));
-
-    opcodes.insert(0x40, Box::new(opcodes::block::blockhash));
-    opcodes.insert(0x41, Box::new(opcodes::block::coinbase));
-    opcodes.insert(0x42, Box::new(opcodes::block::timestamp));
-    opcodes.insert(0x43, Box::new(opcodes::block::number));
-    opcodes.insert(0x44, Box::new(opcodes::block::difficulty));
-    opcodes.insert(0x45, Box::new(opcodes::block::gaslimit));
-    opcodes.insert(0x46, Box::new(opcodes::block::chain));
-    opcodes.insert(0x47, Box::new(opcodes::block::selfbalance));
-    opcodes.insert(0x48, Box::new(opcodes::block::basefee));
-
-    // opcodes.insert(0x0b, Box::new(opcodes::sign_extend));
-    opcodes.insert(0x50, Box::new(opcodes::stack::pop));
-    opcodes.insert(0x51, Box::new(opcodes::memory::mload));
-    opcodes.insert(0x52, Box::new(opcodes::memory::mstore));
-    opcodes.insert(0x53, Box::new(opcodes::memory::mstore8));
-    opcodes.insert(0x54, Box::new(opcodes::storage::sload));
-    opcodes.insert(0x55, Box::new(opcodes::storage::sstore));
-    opcodes.insert(0x56, Box::new(opcodes::stack::jump));
-    opcodes.insert(0x57, Box::new(opcodes::stack::jumpi));
-    opcodes.insert(0x58, Box::new(opcodes::stack::pc));
-    opcodes.insert(0x59, Box::new(opcodes::memory::msize));
-    opcodes.insert(0x5a, Box::new(opcodes::misc::gas));
-    opcodes.insert(0x5b, Box::new(opcodes::stack::jumpdest));
-
-    insert_push_n_functions(&mut opcodes);
-    insert_dup_n_functions(&mut opcodes);
-    insert_swap_n_functions(&mut opcodes);
-    insert_log_n_function(&mut opcodes);
-
-    opcodes.insert(0xf3, Box::new(opcodes::system::return_data));
-    opcodes.insert(0xfe, Box::new(opcodes::misc::invalid));
-
-    opcodes
-}
-
-fn insert_push_n_functions(opcodes: &mut Opcodes) {
-    for n in 1..=32 {
-        opcodes.insert(0x5f + n, generate_push_n_fn(n));
-    }
-}
-
-fn insert_dup_n_functions(opcodes: &mut Opcodes) {
-    for n in 1..=16 {
-        opcodes.insert(0x7f + n, generate_dup_n_fn(n));
-    }
-}
-
-fn insert_swap_n_functions(opcodes: &mut Opcodes) {
-    for n in 1..=16 {
-        opcodes.insert(0x8f + n, generate_swap_n_fn(n));
+/// Converts a stack value to a `usize`, failing if it does not fit
+/// a machine word
+pub fn to_usize(value: U256, error: EvmError) -> Result<usize, EvmError> {
+    if value > U256::from(usize::MAX) {
+        return Err(error);
     }
+    Ok(value.as_usize())
 }
 
-fn insert_log_n_function(opcodes: &mut Opcodes) {
-    for n in 0..=4 {
-        opcodes.insert(0xa0 + n, generate_log_n_fn(n));
+/// Charges the first access to an account in the transaction, on top of
+/// the warm cost charged up front (EIP-2929)
+pub fn access_account(evm: &mut EVM, address: &H160) -> Result<(), EvmError> {
+    let cold = evm.state.warm_address(address);
+    if cold && evm.spec.is_enabled_in(SpecId::Berlin) {
+        evm.gas
+            .record_cost(gas::COLD_ACCOUNT_ACCESS - gas::WARM_STORAGE_READ)?;
     }
+    Ok(())
 }
 
-/// Reads the `execution_bytecode` and returns a vector with all
-/// the indexes in which a jumpdest occurs. This vector naturally is sorted.
+/// Expands the memory so that `size` bytes at `offset` can be accessed,
+/// charging the gas for the expansion, and returns both values as `usize`.
 ///
-/// Computational cost: O(n), where `n` is the length of the bytecode
-pub fn get_jumpdests(execution_bytecode: &Vec<u8>) -> Vec<usize> {
-    let mut pc = 0;
-    let mut jumpdests: Vec<usize> = Vec::new();
+/// Accessing zero bytes never expands the memory, whatever the offset
+pub fn expand_memory(evm: &mut EVM, offset: U256, size: U256) -> Result<(usize, usize), EvmError> {
+    if size.is_zero() {
+        return Ok((0, 0));
+    }
 
-    while pc < execution_bytecode.len() {
-        let opcode = execution_bytecode.get(pc).unwrap();
+    let offset = to_usize(offset, EvmError::InvalidMemoryAccess)?;
+    let size = to_usize(size, EvmError::InvalidMemoryAccess)?;
 
-        if &0x60 <= opcode && opcode <= &0x7f {
-            let offset = opcode - 0x60 + 1;
-            pc += usize::from(offset);
-        } else if opcode == &JUMPDEST {
-            jumpdests.push(pc);
-        }
+    let end = offset
+        .checked_add(size)
+        .ok_or(EvmError::InvalidMemoryAccess)?;
 
-        pc += 1;
-    }
+    let current_words = gas::num_words(evm.memory.len());
+    let new_words = gas::num_words(end);
 
-    jumpdests
-}
+    if new_words > current_words {
+        let expansion_cost = gas::memory_cost(new_words) - gas::memory_cost(current_words);
+        evm.gas.record_cost(expansion_cost)?;
 
-pub fn is_pc_on_jumpdest(evm: &EVM) -> bool {
-    if evm.jumpdests.binary_search(&evm.pc).is_ok() {
-        true
-    } else {
-        false
+        if end > MEMORY_LIMIT {
+            return Err(EvmError::InvalidMemoryAccess);
+        }
+        evm.memory.resize(offset, size);
     }
-}
 
-/// Updates EVM memory size
-pub fn update_msize(evm: &mut EVM, last_byte: usize) {
-    let word_address = last_byte / 32;
-    let new_msize = (word_address + 1) * 32;
-
-    if new_msize > evm.msize {
-        evm.msize = new_msize;
-    }
+    Ok((offset, size))
 }
@@ -1,8 +1,31 @@
+use std::{collections::HashMap, str::FromStr};
+
 use evm_from_scratch::{
-    evm::EVM,
-    utils::types::{EvmTest, ExecutionData},
+    evm::{journal::Checkpoint, receipt::Receipt, EVM},
+    utils::types::{EvmTest, ExecutionData, ExpectedLog, Log},
 };
-use primitive_types::U256;
+use primitive_types::{H160, U256};
+
+fn parse_u256(value: &str) -> U256 {
+    U256::from_str_radix(value, 16).unwrap()
+}
+
+fn parse_address(value: &str) -> H160 {
+    H160::from_str(value.trim_start_matches("0x")).unwrap()
+}
+
+fn log_matches(actual: &Log, expected: &ExpectedLog) -> bool {
+    U256::from_big_endian(actual.address.as_bytes()) == parse_u256(&expected.address)
+        && actual.data == hex::decode(&expected.data).unwrap()
+        && actual.topics.len() == expected.topics.len()
+        && actual
+            .topics
+            .iter()
+            .zip(&expected.topics)
+            .all(|(topic, expected)| {
+                U256::from_big_endian(topic.as_bytes()) == parse_u256(expected)
+            })
+}
 
 fn main() {
     let text = std::fs::read_to_string("./tests.json").unwrap();
@@ -11,10 +34,28 @@ fn main() {
     let total = data.len();
 
     let mut evm = EVM::new();
+    let mut checkpoints: HashMap<&str, Checkpoint> = HashMap::new();
 
     for (index, test) in data.iter().enumerate() {
         println!("Test {} of {}: {}", index + 1, total, test.name);
 
+        evm.spec = test.spec.unwrap_or_default();
+        evm.persist_storage = test.persist_storage;
+        evm.persist_state = test.persist_state;
+        evm.serve_history_hashes = test.serve_history_hashes;
+
+        let stale_checkpoint = match test.revert {
+            Some(ref name) => match checkpoints.get(name.as_str()) {
+                Some(checkpoint) => evm.state.revert_to(*checkpoint).is_err(),
+                None => true,
+            },
+            None => false,
+        };
+
+        if let Some(ref name) = test.checkpoint {
+            checkpoints.insert(name, evm.state.checkpoint());
+        }
+
         let result = evm.execute(ExecutionData {
             bytecode: &hex::decode(&test.code.bin).unwrap(),
             tx: &test.tx,
@@ -25,21 +66,59 @@ fn main() {
         let mut expected_stack: Vec<U256> = Vec::new();
         if let Some(ref stacks) = test.expect.stack {
             for value in stacks {
-                expected_stack.push(U256::from_str_radix(value, 16).unwrap());
+                expected_stack.push(parse_u256(value));
             }
         }
 
-        let mut matching = result.stack.len() == expected_stack.len();
-        if matching {
-            for i in 0..result.stack.len() {
-                if result.stack[i] != expected_stack[i] {
-                    matching = false;
-                    break;
-                }
+        let expected_result = match test.expect.result {
+            Some(ref value) => hex::decode(value).unwrap(),
+            None => result.result.clone(),
+        };
+
+        let expected_gas = match test.expect.gas {
+            Some(ref value) => parse_u256(value).as_u64(),
+            None => result.gas_used,
+        };
+
+        let expected_refund = match test.expect.refund {
+            Some(ref value) => parse_u256(value).as_u64(),
+            None => result.gas_refunded,
+        };
+
+        let matching_removed = test
+            .expect
+            .removed
+            .iter()
+            .flatten()
+            .all(|address| !evm.state.exists(&parse_address(address)));
+
+        let bloom = Receipt::new(&result, 0).logs_bloom;
+        let matching_bloom = match test.expect.bloom {
+            Some(ref value) => bloom.0.as_slice() == hex::decode(value).unwrap(),
+            None => true,
+        };
+
+        let matching_logs = match test.expect.logs {
+            Some(ref logs) => {
+                logs.len() == result.logs.len()
+                    && result
+                        .logs
+                        .iter()
+                        .zip(logs)
+                        .all(|(actual, expected)| log_matches(actual, expected))
             }
-        }
+            None => true,
+        };
 
-        matching = matching && result.success == test.expect.success;
+        let matching = result.stack == expected_stack
+            && result.success == test.expect.success
+            && result.result == expected_result
+            && result.gas_used == expected_gas
+            && result.gas_refunded == expected_refund
+            && matching_removed
+            && matching_bloom
+            && matching_logs
+            && stale_checkpoint == test.expect.stale_checkpoint;
 
         if !matching {
             println!("Instructions: \n{}\n", test.code.asm);
@@ -51,13 +130,36 @@ fn main() {
             }
             println!("]\n");
 
-            println!("Actual success: {:?}", result.success);
+            println!("Actual success: {:?} ({:?})", result.success, result.reason);
             println!("Actual stack: [");
             for v in result.stack {
                 println!("  {:#X},", v);
             }
             println!("]\n");
 
+            println!("Expected return: {}", hex::encode(&expected_result));
+            println!("Actual return: {}\n", hex::encode(&result.result));
+
+            println!("Expected gas used: {}", expected_gas);
+            println!("Actual gas used: {}\n", result.gas_used);
+
+            println!("Expected gas refunded: {}", expected_refund);
+            println!("Actual gas refunded: {}\n", result.gas_refunded);
+
+            println!("Expected removed accounts: {:?}\n", test.expect.removed);
+
+            println!("Expected bloom: {:?}", test.expect.bloom);
+            println!("Actual bloom: {:?}\n", bloom);
+
+            println!("Expected logs: {:#?}", test.expect.logs);
+            println!("Actual logs: {:#?}\n", result.logs);
+
+            println!(
+                "Expected stale checkpoint: {}",
+                test.expect.stale_checkpoint
+            );
+            println!("Actual stale checkpoint: {}\n", stale_checkpoint);
+
             println!("\nHint: {}\n", test.hint);
             println!("Progress: {}/{}\n\n", index, total);
             panic!("Test failed");
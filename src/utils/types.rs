@@ -1,45 +1,125 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
-use primitive_types::U256;
+use primitive_types::{H160, H256, U256};
 use serde::Deserialize;
 
-use crate::evm::EVM;
+use crate::evm::{spec::SpecId, EVM};
 
 #[derive(Debug)]
 pub enum NextAction {
     Continue,
-    Exit(u8),
+    Exit(ExitReason),
 }
 
-#[derive(Debug)]
-pub struct Logs {
-    pub address: String,
-    pub data: String,
-    pub topics: Vec<String>,
+/// Why the execution of some bytecode came to an end
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExitReason {
+    Stop,
+    Return,
+    Revert,
+    /// Exceptional halt: all the gas is consumed and state changes are discarded
+    Error(EvmError),
+}
+
+impl ExitReason {
+    pub fn is_success(&self) -> bool {
+        matches!(self, ExitReason::Stop | ExitReason::Return)
+    }
 }
 
-impl Logs {
-    pub fn new() -> Logs {
-        Logs {
-            address: String::new(),
-            data: String::new(),
-            topics: Vec::new(),
+/// Exceptional halting conditions. None of them should ever abort the host process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvmError {
+    InvalidOpcode(u8),
+    StackUnderflow,
+    StackOverflow,
+    BadJumpDestination,
+    OutOfGas,
+    StaticCallViolation,
+    InvalidMemoryAccess,
+    /// RETURNDATACOPY read past the end of the return data
+    ReturnDataOutOfBounds,
+    /// The maximum depth of nested calls has been reached
+    CallTooDeep,
+    /// The caller cannot afford the value of the call
+    InsufficientBalance,
+    /// The nonce of the creator cannot be incremented any further
+    NonceOverflow,
+    /// A contract already exists at the address of a new contract
+    CreateCollision,
+    /// The init code of a new contract is larger than allowed
+    InitCodeSizeLimit,
+    /// The code returned by the init code is larger than allowed
+    CodeSizeLimit,
+    /// The code returned by the init code starts with the byte 0xEF
+    InvalidCodePrefix,
+    /// The checkpoint was already closed, or belongs to another state
+    StaleCheckpoint,
+    /// The gas limit of the transaction does not cover its intrinsic cost
+    IntrinsicGasTooLow,
+    /// A transaction or block field required by the opcode was not provided
+    MissingField(&'static str),
+    /// A transaction or block field is not a valid hex number
+    InvalidHex(&'static str),
+    /// A transaction or block field does not fit its type
+    FieldTooLarge(&'static str),
+}
+
+impl fmt::Display for EvmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvmError::InvalidOpcode(opcode) => write!(f, "invalid opcode 0x{:02x}", opcode),
+            EvmError::StackUnderflow => write!(f, "stack underflow"),
+            EvmError::StackOverflow => write!(f, "stack overflow"),
+            EvmError::BadJumpDestination => write!(f, "bad jump destination"),
+            EvmError::OutOfGas => write!(f, "out of gas"),
+            EvmError::StaticCallViolation => write!(f, "state modification in static context"),
+            EvmError::InvalidMemoryAccess => write!(f, "invalid memory access"),
+            EvmError::ReturnDataOutOfBounds => write!(f, "return data out of bounds"),
+            EvmError::CallTooDeep => write!(f, "call depth limit reached"),
+            EvmError::InsufficientBalance => write!(f, "insufficient balance"),
+            EvmError::NonceOverflow => write!(f, "nonce overflow"),
+            EvmError::CreateCollision => write!(f, "contract address collision"),
+            EvmError::InitCodeSizeLimit => write!(f, "init code size limit exceeded"),
+            EvmError::CodeSizeLimit => write!(f, "code size limit exceeded"),
+            EvmError::InvalidCodePrefix => write!(f, "code starts with the byte 0xef"),
+            EvmError::StaleCheckpoint => write!(f, "stale checkpoint"),
+            EvmError::IntrinsicGasTooLow => write!(f, "gas limit below the intrinsic cost"),
+            EvmError::MissingField(field) => write!(f, "missing field `{}`", field),
+            EvmError::InvalidHex(field) => write!(f, "field `{}` is not valid hex", field),
+            EvmError::FieldTooLarge(field) => write!(f, "field `{}` is too large", field),
         }
     }
 }
 
-pub type Opcode = Box<dyn Fn(&mut EVM, &ExecutionData) -> NextAction>;
+impl std::error::Error for EvmError {}
+
+pub type OpcodeResult = Result<NextAction, EvmError>;
+
+/// Log emitted by one of LOG0 to LOG4
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Log {
+    /// Account whose code emitted the log
+    pub address: H160,
+    pub topics: Vec<H256>,
+    pub data: Bytes,
+}
+
+pub type Opcode = fn(&mut EVM, &ExecutionData) -> OpcodeResult;
 
 pub type Address = String;
-pub type Opcodes = HashMap<u8, Opcode>;
+
+pub type Bytes = Vec<u8>;
 
 pub type State = HashMap<Address, AccountState>;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct AccountState {
     pub nonce: Option<String>,
     pub balance: Option<String>,
     pub code: Option<Code>,
+    /// Values of the storage slots, by key
+    pub storage: Option<HashMap<String, String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -50,10 +130,26 @@ pub struct EvmTest {
     pub tx: Option<TxData>,
     pub block: Option<BlockData>,
     pub state: Option<State>,
+    /// Hardfork to run the test under, the latest one by default
+    pub spec: Option<SpecId>,
+    /// Whether the test starts with the storage left by the previous one
+    #[serde(default)]
+    pub persist_storage: bool,
+    /// Whether the test starts with the whole state left by the previous one
+    #[serde(default)]
+    pub persist_state: bool,
+    /// Name under which a checkpoint of the state is taken before the test
+    pub checkpoint: Option<String>,
+    /// Name of a checkpoint of an earlier test to revert the state to,
+    /// before the test
+    pub revert: Option<String>,
+    /// Whether BLOCKHASH reads older hashes from the history contract
+    #[serde(default)]
+    pub serve_history_hashes: bool,
     pub expect: Expect,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct Code {
     pub asm: String,
     pub bin: String,
@@ -63,6 +159,28 @@ pub struct Code {
 pub struct Expect {
     pub stack: Option<Vec<String>>,
     pub success: bool,
+    #[serde(rename = "return")]
+    pub result: Option<String>,
+    pub logs: Option<Vec<ExpectedLog>>,
+    /// Gas used by the transaction, intrinsic cost included, once the
+    /// refund is deducted
+    pub gas: Option<String>,
+    /// Gas refunded at the end of the transaction, after the cap
+    pub refund: Option<String>,
+    /// Accounts which no longer exist once the transaction is over
+    pub removed: Option<Vec<String>>,
+    /// Logs bloom of the receipt of the transaction
+    pub bloom: Option<String>,
+    /// Whether reverting to the checkpoint of the test fails
+    #[serde(default)]
+    pub stale_checkpoint: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExpectedLog {
+    pub address: String,
+    pub data: String,
+    pub topics: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -70,9 +188,21 @@ pub struct TxData {
     pub from: Option<String>,
     pub to: Option<String>,
     pub origin: Option<String>,
+    pub gas: Option<String>,
     pub gasprice: Option<String>,
     pub value: Option<String>,
     pub data: Option<String>,
+    /// Versioned hashes of the blobs carried by the transaction
+    pub blobhashes: Option<Vec<String>>,
+    pub accesslist: Option<Vec<AccessListData>>,
+}
+
+/// Address and storage keys the transaction declares it will access
+#[derive(Debug, Deserialize)]
+pub struct AccessListData {
+    pub address: String,
+    #[serde(default)]
+    pub storagekeys: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -83,15 +213,30 @@ pub struct BlockData {
     pub number: Option<String>,
     pub gaslimit: Option<String>,
     pub difficulty: Option<String>,
+    /// The difficulty when missing
+    pub prevrandao: Option<String>,
     pub chainid: Option<String>,
+    /// Hashes of recent blocks, by block number
+    pub blockhashes: Option<HashMap<String, String>>,
 }
 
 #[derive(Debug)]
 pub struct EvmResult {
     pub stack: Vec<U256>,
     pub success: bool,
-    pub logs: Logs,
-    pub result: String,
+    pub reason: ExitReason,
+    /// Gas paid by the sender, intrinsic cost included, once the refund
+    /// is deducted
+    pub gas_used: u64,
+    /// `u64::MAX` when the transaction does not set a gas limit
+    pub gas_remaining: u64,
+    /// Gas given back at the end of the transaction, for clearing storage
+    /// among others. Capped at a fraction of the gas used
+    pub gas_refunded: u64,
+    /// Logs of the execution, in the order they were emitted
+    pub logs: Vec<Log>,
+    /// Data returned by RETURN, or the revert payload of REVERT
+    pub result: Vec<u8>,
 }
 
 #[derive(Debug)]